@@ -44,4 +44,60 @@ pub enum StakingError {
     InvalidReward,
     #[msg("Nft mint not match group mint")]
     NftMintNotMatchGroupMint,
+    #[msg("Reward conversion failed")]
+    RewardConversionFailed,
+    #[msg("Invalid emission schedule")]
+    InvalidEmissionSchedule,
+    #[msg("Invalid fund amount")]
+    InvalidFundAmount,
+    #[msg("Too many reward streams")]
+    TooManyRewardStreams,
+    #[msg("Reward streams active")]
+    RewardStreamsActive,
+    #[msg("Invalid remaining accounts")]
+    InvalidRemainingAccounts,
+    #[msg("Invalid reward vault")]
+    InvalidRewardVault,
+    #[msg("Invalid reward token account")]
+    InvalidRewardTokenAccount,
+    #[msg("Invalid weight table")]
+    InvalidWeightTable,
+    #[msg("Invalid lock tier")]
+    InvalidLockTier,
+    #[msg("Stake locked")]
+    StakeLocked,
+    #[msg("Invalid penalty policy")]
+    InvalidPenaltyPolicy,
+    #[msg("Invalid treasury")]
+    InvalidTreasury,
+    #[msg("Invalid freeze authority")]
+    InvalidFreezeAuthority,
+    #[msg("Invalid nft vault")]
+    InvalidNftVault,
+    #[msg("Invalid nft mint")]
+    InvalidNftMint,
+    #[msg("Invalid nft token account")]
+    InvalidNftTokenAccount,
+    #[msg("Invalid staked record")]
+    InvalidStakedRecord,
+    #[msg("Max per wallet exceeded")]
+    MaxPerWalletExceeded,
+    #[msg("Invalid token amount")]
+    InvalidTokenAmount,
+    #[msg("Invalid token stake cfg")]
+    InvalidTokenStakeCfg,
+    #[msg("Invalid pending admin")]
+    InvalidPendingAdmin,
+    #[msg("Invalid roles")]
+    InvalidRoles,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Operator update out of bounds")]
+    OperatorUpdateOutOfBounds,
+    #[msg("Staking paused")]
+    StakingPaused,
+    #[msg("Withdraw reward paused")]
+    WithdrawRewardPaused,
+    #[msg("Unstaking paused")]
+    UnstakingPaused,
 }
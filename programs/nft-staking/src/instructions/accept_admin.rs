@@ -0,0 +1,27 @@
+use {
+    crate::state::*,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+}
+
+/// Accepts the admin proposed by the current admin.
+pub fn handle_accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let pending_admin = ctx.accounts.pending_admin.key();
+    ctx.accounts.staking_cfg.accept_admin(pending_admin)
+}
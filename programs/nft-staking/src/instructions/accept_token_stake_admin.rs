@@ -0,0 +1,26 @@
+use {
+    crate::state::*,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct AcceptTokenStakeAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(),
+            token_stake_cfg.token_mint.as_ref(),
+            token_stake_cfg.creator.as_ref()
+        ],
+        bump = token_stake_cfg.bump,
+    )]
+    pub token_stake_cfg: Account<'info, TokenStakeCfg>,
+}
+
+/// Accepts the token pool admin proposed by the current admin.
+pub fn handle_accept_token_stake_admin(ctx: Context<AcceptTokenStakeAdmin>) -> Result<()> {
+    let pending_admin = ctx.accounts.pending_admin.key();
+    ctx.accounts.token_stake_cfg.accept_admin(pending_admin)
+}
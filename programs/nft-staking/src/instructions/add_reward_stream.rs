@@ -0,0 +1,138 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+        utils::calc_total_reward_emission,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint,
+            TokenAccount,
+            TokenInterface
+        },
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        mint::token_program = stream_token_program,
+        constraint = stream_mint.key() != staking_cfg.reward_token_mint @ StakingError::InvalidRewardMint,
+    )]
+    pub stream_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = stream_mint,
+        associated_token::authority = reward_vault_authority,
+        associated_token::token_program = stream_token_program,
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = stream_mint,
+        associated_token::authority = admin,
+        associated_token::token_program = stream_token_program,
+    )]
+    pub stream_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The token program of the stream mint, it may differ from the reward token program
+    pub stream_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AddRewardStream<'info> {
+    pub fn transfer_reward(&self, amount: u64) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.stream_token_program.to_account_info(),
+            TransferChecked {
+                from: self.stream_token_account.to_account_info(),
+                to: self.stream_vault.to_account_info(),
+                authority: self.admin.to_account_info(),
+                mint: self.stream_mint.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, amount, self.stream_mint.decimals)
+    }
+}
+
+pub fn handle_add_reward_stream(
+    ctx: Context<AddRewardStream>,
+    reward: u64,
+    staked_end_time: i64,
+) -> Result<()> {
+    let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+    let stream_mint = ctx.accounts.stream_mint.key();
+
+    require_gt!(reward, 0, StakingError::InvalidReward);
+    require_gt!(staked_end_time, current_time, StakingError::InvalidStakedEndTime);
+    require_gte!(staking_cfg.staked_end_time, staked_end_time, StakingError::InvalidStakedEndTime);
+    require_gt!(MAX_REWARD_STREAMS, staking_cfg.reward_streams.len(), StakingError::TooManyRewardStreams);
+    require!(
+        staking_cfg
+            .reward_streams
+            .iter()
+            .all(|stream| stream.reward_token_mint != stream_mint),
+        StakingError::InvalidRewardMint
+    );
+
+    // fund the stream for every slot until the stream ends
+    let total_reward_emission = calc_total_reward_emission(
+        reward,
+        staking_cfg.reward_period,
+        &[],
+        staking_cfg.reward_mode.reward_slots(staking_cfg.max_staked_amount),
+        current_time,
+        staked_end_time,
+    )?;
+    ctx.accounts.transfer_reward(total_reward_emission)?;
+
+    let staking_cfg = &mut ctx.accounts.staking_cfg;
+    staking_cfg.reward_streams.push(RewardStream::init(
+        stream_mint,
+        ctx.accounts.stream_token_program.key(),
+        staked_end_time,
+        reward,
+        current_time,
+    ));
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use {
+    anchor_lang::prelude::*,
+    crate::{
+        state::*,
+        error::*,
+        utils::{
+            nft_helper::load_staked_record,
+            reward_helper::{
+                enable_for_reward,
+                calculate_partial_payout,
+                calculate_reward_for_withdraw,
+                record_owed_reward,
+            },
+        },
+    },
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint, 
+            TokenAccount,
+            TokenInterface},
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct ClaimAll<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(), 
+            staking_cfg.group_mint.key().as_ref(), 
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = !staking_cfg.pause_flags.withdraw_reward @ StakingError::WithdrawRewardPaused,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = staker_position.bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_mint.key() == staking_cfg.reward_token_mint @ StakingError::InvalidRewardMint,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    /// The reward debt of the staker, required once the reward vault can not pay the whole reward
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + RewardDebt::INIT_SPACE,
+        seeds = [
+            REWARD_DEBT_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub reward_debt: Option<Account<'info, RewardDebt>>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimAll<'info> {
+    pub fn transfer_reward(&self, reward_amount: u64) -> Result<()> {
+        let transfer_checked_accounts = TransferChecked {
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_token_account.to_account_info(),
+            authority: self.reward_vault_authority.to_account_info(),
+        };
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(), 
+            transfer_checked_accounts, 
+            signer_seeds
+        );
+        transfer_checked(cpi_ctx, reward_amount, self.reward_mint.decimals)
+    }       
+}
+
+/// Claims the reward token of every staked record passed in the remaining accounts with one transfer.
+/// Records still in their minimum period are skipped, reward streams are claimed with withdraw_reward.
+pub fn handle_claim_all<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClaimAll<'info>>,
+) -> Result<()> {
+    let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+    let StakingCfg{
+        staked_end_time,
+        minimum_period,
+        ..
+    } = **staking_cfg;
+    let staking_cfg_key = staking_cfg.key();
+    let staker_key = ctx.accounts.staker.key();
+
+    require!(!ctx.remaining_accounts.is_empty(), StakingError::InvalidRemainingAccounts);
+
+    let mut total_reward_amount: u64 = 0;
+    let mut total_reward_paid: u128 = 0;
+    for account in ctx.remaining_accounts {
+        let mut staked_record = load_staked_record(account, &staking_cfg_key, &staker_key)?;
+
+        //check if the reward is enabled
+        let (is_reward_enabled, staked_end_time) = enable_for_reward(
+            current_time, 
+            staked_end_time, 
+            minimum_period, 
+            &staked_record
+        )?;
+        if !is_reward_enabled {
+            continue;
+        }
+
+        //calculate the reward
+        let (reward_amount, reward_checkpoint) = calculate_reward_for_withdraw(
+            &ctx.accounts.staking_cfg, 
+            staked_end_time, 
+            staked_record.reward_based_on_staked_time, 
+            staked_record.weight,
+            &ctx.accounts.emission_schedule.segments,
+        )?;
+        total_reward_amount = total_reward_amount
+            .checked_add(reward_amount)
+            .ok_or(StakingError::ProgramAddError)?;
+
+        //update the staked record
+        let reward_paid = reward_checkpoint
+            .checked_sub(staked_record.reward_based_on_staked_time)
+            .ok_or(StakingError::ProgramSubError)?;
+        total_reward_paid = total_reward_paid
+            .checked_add(reward_paid)
+            .ok_or(StakingError::ProgramAddError)?;
+        staked_record.refresh_for_withdraw(
+            reward_checkpoint, 
+            staked_end_time)?;
+        staked_record.exit(&crate::ID)?;
+    }
+
+    //transfer the reward of every staked record at once as far as the reward vault holds it above the owed reward
+    let (reward_paid, reward_owed) = calculate_partial_payout(
+        total_reward_amount,
+        ctx.accounts.reward_vault.amount,
+        ctx.accounts.staking_cfg.total_owed,
+    );
+    if reward_paid > 0 {
+        ctx.accounts.transfer_reward(reward_paid)?;
+    }
+    ctx.accounts.staker_position.add_claimed(reward_paid)?;
+    if reward_owed > 0 {
+        //the rest is owed to the staker
+        record_owed_reward(
+            &mut ctx.accounts.staking_cfg,
+            staking_cfg_key,
+            ctx.accounts.reward_debt.as_deref_mut(),
+            ctx.bumps.reward_debt,
+            staker_key,
+            reward_owed,
+            current_time,
+        )?;
+    }
+
+    //increase the total reward based on staked time
+    let staking_cfg = &mut ctx.accounts.staking_cfg;
+    staking_cfg.increase_total_reward_based_on_staked_time(total_reward_paid)?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use {
+    anchor_lang::prelude::*,
+    crate::{
+        state::*,
+        error::*,
+        utils::reward_helper::calculate_scheduled_reward_liability,
+    },
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint,
+            TokenAccount,
+            TokenInterface},
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct ClaimOwed<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = !staking_cfg.pause_flags.withdraw_reward @ StakingError::WithdrawRewardPaused,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = staker_position.bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
+    #[account(
+        mut,
+        seeds = [
+            REWARD_DEBT_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = reward_debt.bump,
+        has_one = staker,
+    )]
+    pub reward_debt: Account<'info, RewardDebt>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_mint.key() == staking_cfg.reward_token_mint @ StakingError::InvalidRewardMint,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimOwed<'info> {
+    pub fn transfer_reward(&self, reward_amount: u64) -> Result<()> {
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(),
+            TransferChecked {
+                from: self.reward_vault.to_account_info(),
+                mint: self.reward_mint.to_account_info(),
+                to: self.reward_token_account.to_account_info(),
+                authority: self.reward_vault_authority.to_account_info(),
+            },
+            signer_seeds
+        );
+        transfer_checked(cpi_ctx, reward_amount, self.reward_mint.decimals)
+    }
+}
+
+/// Pays the reward owed to the staker as far as the reward vault holds it, the rest stays owed.
+/// The reward the staked records have accrued is kept in the vault so a claim never takes it.
+pub fn handle_claim_owed(ctx: Context<ClaimOwed>) -> Result<()> {
+    let current_time: i64 = ctx.accounts.staking_cfg.reward_period.current_time()?;
+
+    //only the reward vault above the reward the pool still has to pay up to the end time can pay the owed reward
+    let scheduled_reward_liability = calculate_scheduled_reward_liability(
+        &ctx.accounts.staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
+    )?;
+    let reward_vault_available = ctx.accounts.reward_vault.amount.saturating_sub(scheduled_reward_liability);
+    let reward_paid = ctx.accounts.reward_debt.owed.min(reward_vault_available);
+    require_gt!(reward_paid, 0, StakingError::InsufficientReward);
+
+    //transfer the owed reward to the staker
+    ctx.accounts.transfer_reward(reward_paid)?;
+
+    ctx.accounts.reward_debt.pay(reward_paid, current_time)?;
+    ctx.accounts.staking_cfg.decrease_total_owed(reward_paid)?;
+    ctx.accounts.staker_position.add_claimed(reward_paid)?;
+    Ok(())
+}
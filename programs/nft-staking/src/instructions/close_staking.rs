@@ -2,11 +2,23 @@ use {
     crate::{
         state::*,
         error::*,
-        utils::reward_helper::calculate_reward_to_close_return,
+        utils::{
+            checkpoint_rewards,
+            reward_helper::calculate_reward_to_close_return,
+            reward_stream_helper::{
+                calculate_stream_reward_indices,
+                calculate_stream_reward_liability,
+                load_reward_stream_accounts,
+                transfer_stream_reward,
+            },
+        },
     },
     anchor_lang::prelude::*,
     anchor_spl::{
-        associated_token::AssociatedToken,
+        associated_token::{
+            AssociatedToken,
+            get_associated_token_address_with_program_id,
+        },
         token_interface::{
             Mint, 
             TokenAccount, 
@@ -30,7 +42,8 @@ pub struct CloseStaking<'info> {
         seeds = [
             STAKING_CFG_SEED.as_bytes(), 
             staking_cfg.group_mint.key().as_ref(), 
-            admin.key().as_ref()
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
         ],
         bump = staking_cfg.bump,
         has_one = admin,
@@ -38,6 +51,15 @@ pub struct CloseStaking<'info> {
     )]
     pub staking_cfg: Account<'info, StakingCfg>,
 
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
     #[account(
         mut,
         mint::token_program = reward_token_program,
@@ -53,17 +75,25 @@ pub struct CloseStaking<'info> {
     )]
     pub reward_vault: InterfaceAccount<'info, TokenAccount>,
 
+    /// The reward token account of the treasurer, the admin when no treasurer is set
     #[account(
         mut,
-        associated_token::mint = reward_mint,
-        associated_token::authority = admin,
-        associated_token::token_program = reward_token_program,
+        token::mint = reward_mint,
+        token::token_program = reward_token_program,
+        constraint = reward_token_account.key() == get_associated_token_address_with_program_id(
+            &staking_cfg.treasurer(),
+            &reward_mint.key(),
+            &reward_token_program.key(),
+        ) @ StakingError::InvalidRewardTokenAccount,
     )]
     pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This is the authority of the reward vault
     #[account(
-        seeds = [REWARD_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump = staking_cfg.reward_vault_authority_bump,
     )]
     pub reward_vault_authority: UncheckedAccount<'info>,
@@ -88,8 +118,10 @@ impl<'info> CloseStaking<'info> {
             authority: self.reward_vault_authority.to_account_info(),
         };
 
+        let staking_cfg_key = self.staking_cfg.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
             &[self.staking_cfg.reward_vault_authority_bump],
         ]];
 
@@ -105,25 +137,83 @@ impl<'info> CloseStaking<'info> {
 
 
 
-pub fn handle_close_staking(
-    ctx: Context<CloseStaking>,
+/// Closes the pool, the admin closes it and the reward the stakers have not earned goes to the treasurer.
+pub fn handle_close_staking<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CloseStaking<'info>>,
 ) -> Result<()> {
     //get the staked end time
-    let current_time = Clock::get()?.unix_timestamp;
+    let current_time = ctx.accounts.staking_cfg.reward_period.current_time()?;
 
     let staked_end_time = current_time.min(ctx.accounts.staking_cfg.staked_end_time);
 
-    // calculate the reward to return
-    let reward_to_return_scale = calculate_reward_to_close_return(
+    //checkpoint the pool so a frozen accrual stays frozen once the pause is lifted
+    checkpoint_rewards(
+        &mut ctx.accounts.staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
+    )?;
+
+    // calculate the reward to return, the reward owed to the stakers stays in the vault
+    // a vault short of the owed reward has nothing to return
+    let reward_vault_amount = ctx.accounts.reward_vault.amount
+        .saturating_sub(ctx.accounts.staking_cfg.total_owed);
+    let reward_to_return = calculate_reward_to_close_return(
         staked_end_time,
-        ctx.accounts.reward_vault.amount,
-        ctx.accounts.reward_mint.decimals,
+        reward_vault_amount,
         &ctx.accounts.staking_cfg,
+        &ctx.accounts.emission_schedule.segments,
     )?;
 
-    // transfer the reward to the admin
-    if reward_to_return_scale > 0 {
-        ctx.accounts.transfer_reward(reward_to_return_scale)?;
+    // transfer the reward to the treasurer
+    if reward_to_return > 0 {
+        ctx.accounts.transfer_reward(reward_to_return)?;
+    }
+
+    // stop the reward streams and return what the stakers have not earned
+    if !ctx.accounts.staking_cfg.reward_streams.is_empty() {
+        let stream_accounts = load_reward_stream_accounts(
+            ctx.remaining_accounts,
+            &ctx.accounts.staking_cfg.reward_streams,
+            &ctx.accounts.reward_vault_authority.key(),
+            &ctx.accounts.staking_cfg.treasurer(),
+        )?;
+        let stream_reward_indices = calculate_stream_reward_indices(
+            &ctx.accounts.staking_cfg,
+            current_time,
+        )?;
+        let staking_cfg_key = ctx.accounts.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[ctx.accounts.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let staking_cfg = &mut ctx.accounts.staking_cfg;
+        let staked_amount = staking_cfg.staked_amount;
+        for ((stream, accounts), stream_reward_index) in staking_cfg
+            .reward_streams
+            .iter_mut()
+            .zip(&stream_accounts)
+            .zip(stream_reward_indices)
+        {
+            let stream_reward_liability = calculate_stream_reward_liability(
+                stream,
+                stream_reward_index,
+                staked_amount,
+            )?;
+            // nothing accrues after the stream is stopped
+            stream.checkpoint_reward(stream_reward_index, current_time);
+            // a short stream vault has nothing to return
+            let stream_reward_to_return = accounts.vault.amount.saturating_sub(stream_reward_liability);
+            if stream_reward_to_return > 0 {
+                transfer_stream_reward(
+                    accounts,
+                    ctx.accounts.reward_vault_authority.to_account_info(),
+                    signer_seeds,
+                    stream_reward_to_return,
+                )?;
+            }
+            stream.staked_end_time = stream.staked_end_time.min(current_time);
+        }
     }
 
     // update the staking config
@@ -0,0 +1,131 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+        utils::{
+            reward_helper::{calculate_reward_index_increment, descale_reward},
+            token_stake_helper::checkpoint_token_stake,
+        },
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint,
+            TokenAccount,
+            TokenInterface
+        },
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct CloseTokenStaking<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(),
+            token_stake_cfg.token_mint.as_ref(),
+            token_stake_cfg.creator.as_ref()
+        ],
+        bump = token_stake_cfg.bump,
+        has_one = admin,
+        constraint = token_stake_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub token_stake_cfg: Account<'info, TokenStakeCfg>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_token_mint.key() == token_stake_cfg.reward_token_mint @ StakingError::InvalidTokenStakeCfg,
+    )]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_token_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+        ],
+        bump = token_stake_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = admin,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> CloseTokenStaking<'info> {
+    pub fn transfer_reward(&self, amount: u64) -> Result<()> {
+        let token_stake_cfg_key = self.token_stake_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg_key.as_ref(),
+            &[self.token_stake_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(),
+            TransferChecked {
+                from: self.reward_vault.to_account_info(),
+                mint: self.reward_token_mint.to_account_info(),
+                to: self.reward_token_account.to_account_info(),
+                authority: self.reward_vault_authority.to_account_info(),
+            },
+            signer_seeds
+        );
+        transfer_checked(cpi_ctx, amount, self.reward_token_mint.decimals)
+    }
+}
+
+/// Closes the token pool and returns the emission no staker is owed to the admin,
+/// the emission nobody accrued below the minimum stake and the emission after the close.
+/// The stakers keep withdrawing their tokens and the reward accrued so far.
+pub fn handle_close_token_staking(ctx: Context<CloseTokenStaking>) -> Result<()> {
+    let token_stake_cfg = &mut ctx.accounts.token_stake_cfg;
+    let current_time: i64 = token_stake_cfg.reward_period.current_time()?;
+
+    //checkpoint the pool so the emission nobody accrued up to now is counted
+    checkpoint_token_stake(token_stake_cfg, current_time)?;
+
+    //the emission after the close is owed to nobody
+    let staked_end_time = current_time.min(token_stake_cfg.staked_end_time);
+    let future_reward = calculate_reward_index_increment(
+        staked_end_time,
+        token_stake_cfg.staked_end_time,
+        token_stake_cfg.latest_reward,
+        token_stake_cfg.reward_period,
+    )?;
+    let reward_to_return = descale_reward(future_reward)?
+        .checked_add(token_stake_cfg.unallocated_reward)
+        .ok_or(StakingError::ProgramAddError)?
+        .min(ctx.accounts.reward_vault.amount);
+    token_stake_cfg.close_staking(staked_end_time);
+
+    //transfer the reward to the admin
+    if reward_to_return > 0 {
+        ctx.accounts.transfer_reward(reward_to_return)?;
+    }
+    Ok(())
+}
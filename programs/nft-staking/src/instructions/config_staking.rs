@@ -5,7 +5,8 @@ use {
         state::*,
         error::*,
         utils::reward_helper::{
-            calculate_current_reward_from_genesis,
+            calculate_pool_reward_index,
+            calculate_current_reward_rate,
             calculate_total_reward_extend,
         },
     },
@@ -25,25 +26,36 @@ use {
 
 #[derive(Accounts)]
 pub struct ConfigStaking<'info> {
+    /// The admin or the operator
     #[account(mut)]
-    pub admin: Signer<'info>,
+    pub authority: Signer<'info>,
 
     #[account(
         mut,
         seeds = [
             STAKING_CFG_SEED.as_bytes(), 
             staking_cfg.group_mint.key().as_ref(), 
-            admin.key().as_ref()
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
         ],
         bump = staking_cfg.bump,
-        has_one = admin,
+        constraint = staking_cfg.is_operator(&authority.key()) @ StakingError::Unauthorized,
         constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
     )]
     pub staking_cfg: Account<'info, StakingCfg>,
 
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
     #[account(
         init,
-        payer = admin,
+        payer = authority,
         space = 8 + CfgUpdateRecord::INIT_SPACE,
         seeds = [
             CFG_UPDATE_RECORD_SEED.as_bytes(), 
@@ -71,7 +83,10 @@ pub struct ConfigStaking<'info> {
 
     /// CHECK: This is the authority of the reward vault
     #[account(
-        seeds = [REWARD_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump = staking_cfg.reward_vault_authority_bump,
     )]
     pub reward_vault_authority: UncheckedAccount<'info>,
@@ -79,7 +94,7 @@ pub struct ConfigStaking<'info> {
     #[account(
         mut,
         associated_token::mint = reward_mint,
-        associated_token::authority = admin,
+        associated_token::authority = authority,
         associated_token::token_program = reward_token_program,
     )]
     pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
@@ -100,7 +115,7 @@ impl<'info> ConfigStaking<'info> {
             TransferChecked {
                 from: self.reward_token_account.to_account_info(),
                 to: self.reward_vault.to_account_info(),
-                authority: self.admin.to_account_info(),
+                authority: self.authority.to_account_info(),
                 mint: self.reward_mint.to_account_info(),
             },
         );
@@ -117,42 +132,80 @@ impl<'info> ConfigStaking<'info> {
 pub fn handle_config_staking(
     ctx: Context<ConfigStaking>, 
     reward: Option<u64>, 
-    staked_endt_time: Option<i64>
+    staked_end_time: Option<i64>,
+    minimum_period: Option<i64>,
+    max_staked_amount: Option<u64>,
+    max_per_wallet: Option<u64>,
 ) -> Result<()> {
-    let current_time: i64 = Clock::get()?.unix_timestamp;
-
-
     let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
     let StakingCfg{
+        staked_start_time,
         staked_end_time: staked_end_time_before,
-        accumulated_reward,
+        minimum_period: minimum_period_before,
+        max_staked_amount: max_staked_amount_before,
+        staked_amount,
         latest_reward,
         latest_reward_time,
         ..
     } = **staking_cfg;
 
     require_gt!(staked_end_time_before, current_time, StakingError::InvalidStakedEndTime);
-    // check if the staked end time is future 
-    let staked_end_time_i64 = match staked_endt_time {
-        Some(time) if time <= current_time || time <= staked_end_time_before 
+    // an operator only changes the reward, within the bounds set by the admin
+    staking_cfg.check_operator_update(
+        &ctx.accounts.authority.key(),
+        reward,
+        staked_end_time.is_some() || minimum_period.is_some() || max_staked_amount.is_some() || max_per_wallet.is_some(),
+    )?;
+    // check if the staked end time is future, it may be earlier than the current end time
+    let staked_end_time_i64 = match staked_end_time {
+        Some(time) if time <= current_time 
         => return Err(StakingError::InvalidStakedEndTime.into()),
         Some(time) => time,
         None => staked_end_time_before,
     };
 
+    // get the reward rate in effect before this update
+    let current_reward = calculate_current_reward_rate(
+        current_time,
+        latest_reward,
+        latest_reward_time,
+        &ctx.accounts.emission_schedule.segments,
+    );
+
     // get the reward 
     let reward_u64 = match reward {
-        Some(reward) if reward == 0 => return Err(StakingError::InvalidReward.into()),
+        Some(0) => return Err(StakingError::InvalidReward.into()),
         Some(reward) => reward,
-        None => ctx.accounts.cfg_update_record.reward,
+        None => current_reward,
+    };
+
+    // get the minimum period
+    let minimum_period_i64 = match minimum_period {
+        Some(period) if period < 0 => return Err(StakingError::InvalidMinimumPeriod.into()),
+        Some(period) => period,
+        None => minimum_period_before,
+    };
+    let minimum_reward_time = staked_start_time.checked_add(minimum_period_i64).ok_or(StakingError::InvalidMinimumPeriod)?;
+    require_gte!(staked_end_time_i64, minimum_reward_time, StakingError::InvalidMinimumPeriod);
+
+    // get the max staked amount, it can not drop below the weight already staked
+    let max_staked_amount_u64 = match max_staked_amount {
+        Some(amount) if amount == 0 || (amount as u128) * (BASE_WEIGHT as u128) < staked_amount as u128 
+        => return Err(StakingError::InvalidMaxStakedAmount.into()),
+        // the reward streams are only funded for the current max staked amount
+        Some(amount) if amount > max_staked_amount_before 
+            && ctx.accounts.staking_cfg.has_active_reward_streams(current_time)
+        => return Err(StakingError::RewardStreamsActive.into()),
+        Some(amount) => amount,
+        None => max_staked_amount_before,
     };
 
     // calculate the current reward from genesis
-    let current_reward_from_genesis = calculate_current_reward_from_genesis(
-        accumulated_reward,
+    let current_reward_from_genesis = calculate_pool_reward_index(
+        &ctx.accounts.staking_cfg,
         current_time,
-        latest_reward,
-        latest_reward_time,
+        &ctx.accounts.emission_schedule.segments,
     )?;
 
     // calculate the total reward needed to be added
@@ -160,21 +213,26 @@ pub fn handle_config_staking(
         reward_u64,
         current_time,
         staked_end_time_i64,
+        max_staked_amount_u64,
         current_reward_from_genesis,
         &ctx.accounts.staking_cfg,
+        &ctx.accounts.emission_schedule.segments,
     )?;
+    // the reward owed to the stakers stays in the vault
+    let total_reward_needed = total_reward_needed
+        .checked_add(ctx.accounts.staking_cfg.total_owed)
+        .ok_or(StakingError::ProgramAddError)?;
 
 
-    let total_reward_needed_scale = total_reward_needed.checked_mul(10_u64.pow(ctx.accounts.reward_mint.decimals as u32)).ok_or(StakingError::ProgramMulError)?;
     msg!("total_reward_needed: {}", total_reward_needed);
     msg!("reward_vault balance: {}", ctx.accounts.reward_vault.amount);
-    msg!("total_reward_needed_scale: {}", total_reward_needed_scale);
 
-    if total_reward_needed_scale > ctx.accounts.reward_vault.amount {
+    if total_reward_needed > ctx.accounts.reward_vault.amount {
         // transfer the reward to the reward vault
-        let reward_transfer_amount = total_reward_needed_scale.checked_sub(ctx.accounts.reward_vault.amount).ok_or(StakingError::ProgramSubError)?;
+        let reward_transfer_amount = total_reward_needed.checked_sub(ctx.accounts.reward_vault.amount).ok_or(StakingError::ProgramSubError)?;
         ctx.accounts.transfer_reward(reward_transfer_amount)?;
     }
+    // the over-funded reward stays in the vault until the treasurer withdraws the surplus
 
     let staking_cfg = &mut ctx.accounts.staking_cfg;
 
@@ -183,16 +241,20 @@ pub fn handle_config_staking(
     **cfg_update_record = CfgUpdateRecord::init(
         ctx.bumps.cfg_update_record,
         staking_cfg.updated_times.checked_add(1).ok_or(StakingError::ProgramAddError)?,
-        staking_cfg.latest_reward,
+        current_reward,
         staking_cfg.latest_reward_time,
     );
 
     // update the staking config
     staking_cfg.updated_times = staking_cfg.updated_times.checked_add(1).ok_or(StakingError::ProgramAddError)?;
-    staking_cfg.accumulated_reward = current_reward_from_genesis;
-    staking_cfg.latest_reward = reward_u64;
-    staking_cfg.latest_reward_time = current_time;
+    staking_cfg.checkpoint_reward(current_reward_from_genesis, reward_u64, current_time);
     staking_cfg.staked_end_time = staked_end_time_i64;
+    staking_cfg.minimum_period = minimum_period_i64;
+    staking_cfg.max_staked_amount = max_staked_amount_u64;
+    // a zero max per wallet lifts the cap, stakers above a lowered cap keep their nfts staked
+    if let Some(max_per_wallet) = max_per_wallet {
+        staking_cfg.max_per_wallet = max_per_wallet;
+    }
 
 
     Ok(())
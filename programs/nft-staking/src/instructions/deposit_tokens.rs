@@ -0,0 +1,128 @@
+use {
+    anchor_lang::prelude::*,
+    crate::{
+        state::*,
+        error::*,
+        utils::token_stake_helper::deposit_token_stake,
+    },
+    anchor_spl::{
+        token_interface::{
+            Mint,
+            TokenAccount,
+            TokenInterface},
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct DepositTokens<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(),
+            token_stake_cfg.token_mint.as_ref(),
+            token_stake_cfg.creator.as_ref()
+        ],
+        bump = token_stake_cfg.bump,
+        constraint = token_stake_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub token_stake_cfg: Account<'info, TokenStakeCfg>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + TokenStakeRecord::INIT_SPACE,
+        seeds = [
+            TOKEN_STAKE_RECORD_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub token_stake_record: Account<'info, TokenStakeRecord>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = token_mint.key() == token_stake_cfg.token_mint @ StakingError::InvalidTokenStakeCfg,
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::authority = token_vault_authority,
+        associated_token::mint = token_mint,
+        associated_token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the staked token vault
+    #[account(
+        seeds = [
+            TOKEN_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+        ],
+        bump = token_stake_cfg.token_vault_authority_bump,
+    )]
+    pub token_vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DepositTokens<'info> {
+    pub fn transfer_tokens(&self, amount: u64) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.token_account.to_account_info(),
+                mint: self.token_mint.to_account_info(),
+                to: self.token_vault.to_account_info(),
+                authority: self.staker.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, amount, self.token_mint.decimals)
+    }
+}
+
+/// Deposits `amount` of the staked token, the weight of the record is the amount received by the vault.
+pub fn handle_deposit_tokens(ctx: Context<DepositTokens>, amount: u64) -> Result<()> {
+    let current_time: i64 = ctx.accounts.token_stake_cfg.reward_period.current_time()?;
+
+    //CHECK THE STAKE END TIME IS EXPIRED
+    require_gt!(ctx.accounts.token_stake_cfg.staked_end_time, current_time, StakingError::StakeEndTimeExpired);
+    require_gt!(amount, 0, StakingError::InvalidTokenAmount);
+
+    //transfer the tokens to the vault, a transfer fee is not credited to the staker
+    let vault_amount_before = ctx.accounts.token_vault.amount;
+    ctx.accounts.transfer_tokens(amount)?;
+    ctx.accounts.token_vault.reload()?;
+    let received_amount = ctx.accounts.token_vault.amount
+        .checked_sub(vault_amount_before)
+        .ok_or(StakingError::ProgramSubError)?;
+
+    let token_stake_cfg_key = ctx.accounts.token_stake_cfg.key();
+    deposit_token_stake(
+        &mut ctx.accounts.token_stake_cfg,
+        &mut ctx.accounts.token_stake_record,
+        ctx.bumps.token_stake_record,
+        token_stake_cfg_key,
+        ctx.accounts.staker.key(),
+        received_amount,
+        current_time,
+    )
+}
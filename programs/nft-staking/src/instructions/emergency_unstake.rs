@@ -0,0 +1,220 @@
+use {
+    crate::{
+        error::*,
+        state::*,
+        utils::checkpoint_rewards,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::{
+        token_2022::{
+            close_account,
+            revoke,
+            thaw_account,
+            transfer_checked,
+            CloseAccount,
+            Revoke,
+            ThawAccount,
+            Token2022,
+            TransferChecked
+        },
+        token_interface,
+    },
+};
+
+#[derive(Accounts)]
+pub struct EmergencyUnstake<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = staker_position.bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKED_RECORD_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            nft_mint.key().as_ref()
+        ],
+        bump = staked_record.bump,
+        has_one = staker,
+        has_one = nft_mint,
+        close = staker,
+    )]
+    pub staked_record: Account<'info, StakedRecord>,
+
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = token_2022_program,
+    )]
+    pub nft_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mint::token_program = token_2022_program,
+    )]
+    pub nft_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: This is the authority of the nft vault and the freeze authority of soft staked nfts
+    #[account(
+        seeds = [
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.nft_vault_authority_bump,
+    )]
+    pub nft_vault_authority: UncheckedAccount<'info>,
+
+    /// The nft vault, omitted when the nft is soft staked
+    #[account(
+        mut,
+        associated_token::authority = nft_vault_authority,
+        associated_token::mint = nft_mint,
+        associated_token::token_program = token_2022_program,
+        constraint = nft_vault.amount == 1 @ StakingError::InvalidNftAmount,
+    )]
+    pub nft_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_2022_program: Program<'info, Token2022>,
+}
+
+
+impl<'info> EmergencyUnstake<'info> {
+    pub fn transfer_nft(&self, nft_vault: &InterfaceAccount<'info, token_interface::TokenAccount>) -> Result<()> {
+        let transfer_checked_accounts = TransferChecked {
+            from: nft_vault.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            to: self.nft_token_account.to_account_info(),
+            authority: self.nft_vault_authority.to_account_info(),
+        };
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.nft_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_2022_program.to_account_info(),
+            transfer_checked_accounts,
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, 1, self.nft_mint.decimals)
+    }
+
+    pub fn close_nft_vault(&self, nft_vault: &InterfaceAccount<'info, token_interface::TokenAccount>) -> Result<()> {
+        let close_account_accounts = CloseAccount {
+            account: nft_vault.to_account_info(),
+            destination: self.staker.to_account_info(),
+            authority: self.nft_vault_authority.to_account_info(),
+        };
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.nft_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_2022_program.to_account_info(),
+            close_account_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)
+    }
+
+    pub fn thaw_nft(&self) -> Result<()> {
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.nft_vault_authority_bump],
+        ]];
+        let thaw_ctx = CpiContext::new_with_signer(
+            self.token_2022_program.to_account_info(),
+            ThawAccount {
+                account: self.nft_token_account.to_account_info(),
+                mint: self.nft_mint.to_account_info(),
+                authority: self.nft_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        thaw_account(thaw_ctx)?;
+
+        //REVOKE THE POOL DELEGATION
+        let revoke_ctx = CpiContext::new(
+            self.token_2022_program.to_account_info(),
+            Revoke {
+                source: self.nft_token_account.to_account_info(),
+                authority: self.staker.to_account_info(),
+            },
+        );
+        revoke(revoke_ctx)
+    }
+}
+
+/// Returns the nft without paying any reward, the pending reward and stream rewards are forfeited.
+/// It is not blocked by the unstake pause nor by the lock, the lock only boosts the forfeited reward,
+/// so a staker can always recover the nft.
+pub fn handle_emergency_unstake(ctx: Context<EmergencyUnstake>) -> Result<()> {
+    let current_time: i64 = ctx.accounts.staking_cfg.reward_period.current_time()?;
+
+    //a shared pool splits the reward by the staked weight, checkpoint it before the weight changes
+    //a failing checkpoint must not keep the nft, the accrual is frozen at the last checkpoint instead
+    if ctx.accounts.staking_cfg.reward_mode == RewardMode::Shared
+        && checkpoint_rewards(
+            &mut ctx.accounts.staking_cfg,
+            current_time,
+            &ctx.accounts.emission_schedule.segments,
+        ).is_err()
+    {
+        ctx.accounts.staking_cfg.freeze_accrual_at_checkpoint();
+    }
+
+    //return the nft to the staker
+    if ctx.accounts.staked_record.soft_staked {
+        ctx.accounts.thaw_nft()?;
+    } else {
+        let nft_vault = ctx.accounts.nft_vault.as_ref().ok_or(StakingError::InvalidNftVault)?;
+        ctx.accounts.transfer_nft(nft_vault)?;
+        //close the nft vault
+        ctx.accounts.close_nft_vault(nft_vault)?;
+    }
+
+    //remove the checkpoints of the staked record, the forfeited reward stays in the vaults
+    let staked_record = &ctx.accounts.staked_record;
+    ctx.accounts.staking_cfg.forfeit_staked_record(staked_record);
+
+    //update the staker position
+    ctx.accounts.staker_position.forfeit_stake(1, staked_record.weight);
+
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+        utils::reward_helper::{
+            calculate_pool_reward_index,
+            calculate_current_reward_rate,
+            calculate_end_time_extension,
+            calculate_reward_raise,
+        },
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint,
+            TokenAccount,
+            TokenInterface
+        },
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        mut,
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        init_if_needed,
+        payer = sponsor,
+        space = 8 + SponsorRecord::INIT_SPACE,
+        seeds = [
+            SPONSOR_RECORD_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            sponsor.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub sponsor_record: Account<'info, SponsorRecord>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_mint.key() == staking_cfg.reward_token_mint @ StakingError::InvalidRewardMint,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = sponsor,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FundRewards<'info> {
+    pub fn transfer_reward(&self, amount: u64) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.reward_token_program.to_account_info(),
+            TransferChecked {
+                from: self.reward_token_account.to_account_info(),
+                to: self.reward_vault.to_account_info(),
+                authority: self.sponsor.to_account_info(),
+                mint: self.reward_mint.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)
+    }
+}
+
+pub fn handle_fund_rewards(
+    ctx: Context<FundRewards>,
+    amount: u64,
+    fund_mode: FundMode,
+) -> Result<()> {
+    let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+    let StakingCfg{
+        staked_end_time,
+        max_staked_amount,
+        latest_reward,
+        latest_reward_time,
+        reward_period,
+        reward_mode,
+        ..
+    } = **staking_cfg;
+    let reward_slots = reward_mode.reward_slots(max_staked_amount);
+
+    require_gt!(amount, 0, StakingError::InvalidFundAmount);
+    require_gt!(staked_end_time, current_time, StakingError::StakeEndTimeExpired);
+
+    match fund_mode {
+        FundMode::ExtendEndTime => {
+            // extend at the rate in effect when the staking ends
+            let end_reward = calculate_current_reward_rate(
+                staked_end_time,
+                latest_reward,
+                latest_reward_time,
+                &ctx.accounts.emission_schedule.segments,
+            );
+            let extension = calculate_end_time_extension(
+                amount,
+                end_reward,
+                reward_period,
+                reward_slots,
+            )?;
+            require_gt!(extension, 0, StakingError::InvalidFundAmount);
+
+            let staking_cfg = &mut ctx.accounts.staking_cfg;
+            staking_cfg.staked_end_time = staked_end_time
+                .checked_add(extension)
+                .ok_or(StakingError::ProgramAddError)?;
+        }
+        FundMode::RaiseReward => {
+            let reward_raise = calculate_reward_raise(
+                amount,
+                current_time,
+                staked_end_time,
+                reward_period,
+                reward_slots,
+            )?;
+            require_gt!(reward_raise, 0, StakingError::InvalidFundAmount);
+
+            // checkpoint the reward index before the rate changes
+            let current_reward_from_genesis = calculate_pool_reward_index(
+                staking_cfg,
+                current_time,
+                &ctx.accounts.emission_schedule.segments,
+            )?;
+            let current_reward = calculate_current_reward_rate(
+                current_time,
+                latest_reward,
+                latest_reward_time,
+                &ctx.accounts.emission_schedule.segments,
+            );
+
+            ctx.accounts
+                .emission_schedule
+                .raise_future_rewards(current_time, reward_raise)?;
+
+            let staking_cfg = &mut ctx.accounts.staking_cfg;
+            staking_cfg.checkpoint_reward(
+                current_reward_from_genesis,
+                current_reward
+                    .checked_add(reward_raise)
+                    .ok_or(StakingError::ProgramAddError)?,
+                current_time,
+            );
+        }
+    }
+
+    // transfer the reward from the sponsor to the reward vault
+    ctx.accounts.transfer_reward(amount)?;
+
+    // record the sponsor contribution
+    let staking_cfg_key = ctx.accounts.staking_cfg.key();
+    let sponsor_key = ctx.accounts.sponsor.key();
+    ctx.accounts.sponsor_record.add_funding(
+        ctx.bumps.sponsor_record,
+        staking_cfg_key,
+        sponsor_key,
+        amount,
+        current_time,
+    )?;
+
+    Ok(())
+}
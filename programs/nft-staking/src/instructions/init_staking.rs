@@ -1,6 +1,9 @@
 use {
     crate::{
-        utils::calc_total_reward_emission, 
+        utils::{
+            calc_total_reward_emission,
+            reward_helper::validate_rate_segments,
+        },
         state::*,
         error::*
     }, 
@@ -21,6 +24,7 @@ use {
 
 
 #[derive(Accounts)]
+#[instruction(pool_id: u64)]
 pub struct InitStaking<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -32,12 +36,37 @@ pub struct InitStaking<'info> {
         seeds = [
             STAKING_CFG_SEED.as_bytes(), 
             group_mint.key().as_ref(), 
-            payer.key().as_ref()
+            payer.key().as_ref(),
+            pool_id.to_le_bytes().as_ref()
         ],
         bump,
     )]
     pub staking_cfg: Account<'info, StakingCfg>,
 
+    #[account(
+        init,
+        payer=payer,
+        space=8 + EmissionSchedule::INIT_SPACE,
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        init,
+        payer=payer,
+        space=8 + WeightTable::INIT_SPACE,
+        seeds = [
+            WEIGHT_TABLE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub weight_table: Account<'info, WeightTable>,
+
     #[account(
         mut,
         associated_token::mint = reward_token_mint,
@@ -62,14 +91,20 @@ pub struct InitStaking<'info> {
 
     /// CHECK: This is the authority of the reward vault
     #[account(
-        seeds = [REWARD_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump,
     )]
     pub reward_vault_authority: AccountInfo<'info>,
 
     /// CHECK: This is the authority of the staked vault
     #[account(
-        seeds = [NFT_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump,
     )]
     pub nft_vault_authority: AccountInfo<'info>,
@@ -104,15 +139,21 @@ impl<'info> InitStaking<'info> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_init_staking(
     ctx: Context<InitStaking>,
+    pool_id: u64,
     reward: u64,
+    reward_period: RewardPeriod,
+    reward_mode: RewardMode,
     staked_start_time: i64,
     staked_end_time: i64,
     minimum_period: i64,
     max_staked_amount: u64,
+    max_per_wallet: Option<u64>,
+    segments: Vec<RateSegment>,
 ) -> Result<()> {
-    let current_time = Clock::get()?.unix_timestamp;
+    let current_time = reward_period.current_time()?;
     let reward_token_decimals = ctx.accounts.reward_token_mint.decimals;
     //CHECK THE CONDITIONS
     require_gt!(staked_end_time, staked_start_time, StakingError::InvalidStakedEndTime);
@@ -122,10 +163,17 @@ pub fn handle_init_staking(
 
     let minimum_reward_time = staked_start_time.checked_add(minimum_period).ok_or(StakingError::InvalidMinimumPeriod)?;
     require_gte!(staked_end_time, minimum_reward_time, StakingError::InvalidMinimumPeriod);
+    validate_rate_segments(&segments, current_time, staked_end_time)?;
 
     //CALCULATE THE TOTAL REWARD EMISSION
-    let mut total_reward_emission = calc_total_reward_emission(reward, max_staked_amount, staked_start_time, staked_end_time)?;
-    total_reward_emission = total_reward_emission.checked_mul(10_u64.pow(reward_token_decimals as u32)).ok_or(StakingError::ProgramMulError)?;
+    let total_reward_emission = calc_total_reward_emission(
+        reward,
+        reward_period,
+        &segments,
+        reward_mode.reward_slots(max_staked_amount),
+        staked_start_time,
+        staked_end_time,
+    )?;
     msg!("total_reward_emission: {}", total_reward_emission);
     //TRANSFER THE REWARD TOKEN TO THE VAULT
     transfer_checked(ctx.accounts.transfer_reward_token(), total_reward_emission, reward_token_decimals)?;
@@ -135,17 +183,36 @@ pub fn handle_init_staking(
     **staking_cfg = StakingCfg::init(
         ctx.bumps.staking_cfg,
         ctx.accounts.payer.key(),
+        pool_id,
         ctx.accounts.group_mint.key(),
         ctx.accounts.reward_token_mint.key(),
         ctx.bumps.reward_vault_authority,
         ctx.bumps.nft_vault_authority,
         max_staked_amount,
+        max_per_wallet.unwrap_or_default(),
         staked_start_time,
         staked_end_time,
         reward,
+        reward_period,
+        reward_mode,
         current_time,
         minimum_period,
     );
 
+    //INIT THE EMISSION SCHEDULE
+    let emission_schedule = &mut ctx.accounts.emission_schedule;
+    **emission_schedule = EmissionSchedule::init(
+        ctx.bumps.emission_schedule,
+        ctx.accounts.staking_cfg.key(),
+        segments,
+    );
+
+    //INIT THE WEIGHT TABLE, EVERY NFT HAS THE BASE WEIGHT UNTIL THE ADMIN SETS IT
+    let weight_table = &mut ctx.accounts.weight_table;
+    **weight_table = WeightTable::init(
+        ctx.bumps.weight_table,
+        ctx.accounts.staking_cfg.key(),
+    );
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+use {
+    crate::{
+        utils::calc_total_reward_emission,
+        state::*,
+        error::*
+    }, 
+    anchor_lang::prelude::*, 
+    anchor_spl::{
+        associated_token::AssociatedToken, 
+        token_2022::{
+            transfer_checked, 
+            TransferChecked, 
+        },
+        token_interface::{
+            self,
+            TokenInterface
+        }
+    }, 
+};
+
+
+#[derive(Accounts)]
+pub struct InitTokenStaking<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init, 
+        payer=payer, 
+        space=8 + TokenStakeCfg::INIT_SPACE,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(), 
+            token_mint.key().as_ref(), 
+            payer.key().as_ref()
+        ],
+        bump,
+    )]
+    pub token_stake_cfg: Account<'info, TokenStakeCfg>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub payer_reward_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mint::token_program = token_program,
+    )]
+    pub token_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+    )]
+    pub reward_token_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer=payer, 
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = reward_vault_authority,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub reward_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer=payer, 
+        associated_token::mint = token_mint,
+        associated_token::authority = token_vault_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// CHECK: This is the authority of the staked token vault
+    #[account(
+        seeds = [
+            TOKEN_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub token_vault_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitTokenStaking<'info> {
+    pub fn transfer_reward_token(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let transfer_checked = TransferChecked {
+            from: self.payer_reward_token_account.to_account_info(),
+            mint: self.reward_token_mint.to_account_info(),
+            to: self.reward_vault.to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+       
+        CpiContext::new(
+            self.reward_token_program.to_account_info(),
+            transfer_checked
+        )
+    }
+}
+
+/// Creates a token staking pool sharing `reward` of the reward token per reward period between the staked tokens.
+pub fn handle_init_token_staking(
+    ctx: Context<InitTokenStaking>,
+    reward: u64,
+    reward_period: RewardPeriod,
+    staked_start_time: i64,
+    staked_end_time: i64,
+) -> Result<()> {
+    let current_time = reward_period.current_time()?;
+    let reward_token_decimals = ctx.accounts.reward_token_mint.decimals;
+    //CHECK THE CONDITIONS
+    require_gt!(reward, 0, StakingError::InvalidReward);
+    require_gt!(staked_end_time, staked_start_time, StakingError::InvalidStakedEndTime);
+    require_gt!(staked_end_time, current_time, StakingError::InvalidStakedEndTime);
+
+    //CALCULATE THE TOTAL REWARD EMISSION, THE RATE IS PAID ONCE FOR THE WHOLE POOL
+    let total_reward_emission = calc_total_reward_emission(
+        reward,
+        reward_period,
+        &[],
+        1,
+        staked_start_time,
+        staked_end_time,
+    )?;
+    //TRANSFER THE REWARD TOKEN TO THE VAULT
+    transfer_checked(ctx.accounts.transfer_reward_token(), total_reward_emission, reward_token_decimals)?;
+
+    //NOTHING ACCRUES BELOW ONE WHOLE STAKED TOKEN
+    let min_staked_amount = 10u64
+        .checked_pow(ctx.accounts.token_mint.decimals as u32)
+        .ok_or(StakingError::ProgramMulError)?;
+
+    //INIT THE TOKEN STAKE CFG
+    let token_stake_cfg = &mut ctx.accounts.token_stake_cfg;
+    **token_stake_cfg = TokenStakeCfg::init(
+        ctx.bumps.token_stake_cfg,
+        ctx.accounts.payer.key(),
+        ctx.accounts.token_mint.key(),
+        ctx.accounts.reward_token_mint.key(),
+        ctx.bumps.reward_vault_authority,
+        ctx.bumps.token_vault_authority,
+        staked_start_time,
+        staked_end_time,
+        reward,
+        reward_period,
+        current_time,
+        min_staked_amount,
+    );
+
+    Ok(())
+}
@@ -1,14 +1,58 @@
 pub mod init_staking;
 pub mod stake;
+pub mod stake_many;
 pub mod unstake;
+pub mod unstake_many;
+pub mod emergency_unstake;
 pub mod withdraw_reward;
+pub mod claim_all;
+pub mod claim_owed;
 pub mod config_staking;
+pub mod withdraw_surplus;
 pub mod close_staking;
+pub mod fund_rewards;
+pub mod add_reward_stream;
+pub mod set_weight_table;
+pub mod set_lock_tiers;
+pub mod set_penalty_policy;
+pub mod propose_admin;
+pub mod accept_admin;
+pub mod set_roles;
+pub mod pause_staking;
+pub mod resume_staking;
+pub mod init_token_staking;
+pub mod deposit_tokens;
+pub mod withdraw_tokens;
+pub mod close_token_staking;
+pub mod propose_token_stake_admin;
+pub mod accept_token_stake_admin;
 
 pub use init_staking::*;
 pub use stake::*;
+pub use stake_many::*;
 pub use unstake::*;
+pub use unstake_many::*;
+pub use emergency_unstake::*;
 pub use withdraw_reward::*;
+pub use claim_all::*;
+pub use claim_owed::*;
 pub use config_staking::*;
+pub use withdraw_surplus::*;
 pub use close_staking::*;
+pub use fund_rewards::*;
+pub use add_reward_stream::*;
+pub use set_weight_table::*;
+pub use set_lock_tiers::*;
+pub use set_penalty_policy::*;
+pub use propose_admin::*;
+pub use accept_admin::*;
+pub use set_roles::*;
+pub use pause_staking::*;
+pub use resume_staking::*;
+pub use init_token_staking::*;
+pub use deposit_tokens::*;
+pub use withdraw_tokens::*;
+pub use close_token_staking::*;
+pub use propose_token_stake_admin::*;
+pub use accept_token_stake_admin::*;
 
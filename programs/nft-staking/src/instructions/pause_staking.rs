@@ -0,0 +1,35 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+    },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct PauseStaking<'info> {
+    /// The admin or the pauser
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = staking_cfg.is_pauser(&authority.key()) @ StakingError::Unauthorized,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+}
+
+/// Pauses the actions set in `pause_flags`, only the admin can resume them.
+pub fn handle_pause_staking(ctx: Context<PauseStaking>, pause_flags: PauseFlags) -> Result<()> {
+    let staking_cfg = &mut ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+    staking_cfg.pause(pause_flags, current_time);
+    Ok(())
+}
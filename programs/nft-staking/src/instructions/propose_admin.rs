@@ -0,0 +1,27 @@
+use {
+    crate::state::*,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+}
+
+/// Proposes a new admin, the admin is only changed once the new admin accepts it.
+pub fn handle_propose_admin(ctx: Context<ProposeAdmin>, pending_admin: Pubkey) -> Result<()> {
+    ctx.accounts.staking_cfg.propose_admin(pending_admin)
+}
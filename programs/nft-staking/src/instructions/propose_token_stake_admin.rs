@@ -0,0 +1,26 @@
+use {
+    crate::state::*,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct ProposeTokenStakeAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(),
+            token_stake_cfg.token_mint.as_ref(),
+            token_stake_cfg.creator.as_ref()
+        ],
+        bump = token_stake_cfg.bump,
+        has_one = admin,
+    )]
+    pub token_stake_cfg: Account<'info, TokenStakeCfg>,
+}
+
+/// Proposes a new admin of the token pool, the admin is only changed once the new admin accepts it.
+pub fn handle_propose_token_stake_admin(ctx: Context<ProposeTokenStakeAdmin>, pending_admin: Pubkey) -> Result<()> {
+    ctx.accounts.token_stake_cfg.propose_admin(pending_admin)
+}
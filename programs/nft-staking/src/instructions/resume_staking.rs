@@ -0,0 +1,47 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+        utils::checkpoint_rewards,
+    },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct ResumeStaking<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+}
+
+/// Resumes the actions set in `pause_flags`, a frozen accrual does not pay for the paused time.
+pub fn handle_resume_staking(ctx: Context<ResumeStaking>, pause_flags: PauseFlags) -> Result<()> {
+    let staking_cfg = &mut ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+
+    //checkpoint the frozen reward so the accrual restarts from now
+    checkpoint_rewards(staking_cfg, current_time, &ctx.accounts.emission_schedule.segments)?;
+    staking_cfg.resume(pause_flags, current_time);
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+    },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetLockTiers<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+}
+
+/// Sets the lock tiers of nfts staked from now on, staked nfts keep their lock and multiplier.
+pub fn handle_set_lock_tiers(
+    ctx: Context<SetLockTiers>,
+    lock_tiers: Vec<LockTier>,
+) -> Result<()> {
+    ctx.accounts.staking_cfg.set_lock_tiers(lock_tiers)
+}
@@ -0,0 +1,44 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::TokenAccount,
+};
+
+#[derive(Accounts)]
+pub struct SetPenaltyPolicy<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        constraint = treasury.mint == staking_cfg.reward_token_mint @ StakingError::InvalidTreasury,
+    )]
+    pub treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+/// Sets how the reward of an nft unstaked before the minimum period is paid.
+pub fn handle_set_penalty_policy(
+    ctx: Context<SetPenaltyPolicy>,
+    penalty_policy: PenaltyPolicy,
+    penalty_rate: u16,
+) -> Result<()> {
+    let treasury = ctx.accounts.treasury.as_ref().map(|treasury| treasury.key());
+    ctx.accounts
+        .staking_cfg
+        .set_penalty_policy(penalty_policy, penalty_rate, treasury)
+}
@@ -0,0 +1,31 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+    },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetRoles<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+}
+
+/// Delegates the operator, treasurer and pauser roles, a default key leaves the role to the admin.
+pub fn handle_set_roles(ctx: Context<SetRoles>, roles: PoolRoles) -> Result<()> {
+    ctx.accounts.staking_cfg.set_roles(roles)
+}
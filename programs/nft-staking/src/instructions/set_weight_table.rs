@@ -0,0 +1,47 @@
+use {
+    crate::{
+        state::*,
+        error::*,
+    },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetWeightTable<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        has_one = admin,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        mut,
+        seeds = [
+            WEIGHT_TABLE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = weight_table.bump,
+    )]
+    pub weight_table: Account<'info, WeightTable>,
+}
+
+/// Sets the weights of nfts staked from now on, staked nfts keep the weight they were staked with.
+pub fn handle_set_weight_table(
+    ctx: Context<SetWeightTable>,
+    metadata_key: String,
+    default_weight: u64,
+    entries: Vec<WeightEntry>,
+) -> Result<()> {
+    ctx.accounts
+        .weight_table
+        .update(metadata_key, default_weight, entries)
+}
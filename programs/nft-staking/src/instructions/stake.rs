@@ -1,22 +1,27 @@
 use {
     anchor_lang::prelude::*,
-    spl_token_group_interface::state::TokenGroupMember,
     crate::{
         state::*,
         error::*,
-        utils::reward_helper::calculate_current_reward_from_genesis,
+        utils::{
+            checkpoint_rewards,
+            nft_helper::stake_nft,
+        },
     },
     anchor_spl::{
         associated_token::AssociatedToken,
         token_2022::{
+            approve,
+            freeze_account,
             transfer_checked,
+            Approve,
+            FreezeAccount,
             TransferChecked,
             Token2022,
         },
         token_interface::{
             self,
             TokenInterface,
-            get_mint_extension_data
         },
     },
 };
@@ -32,13 +37,46 @@ pub struct Stake<'info> {
         seeds = [
             STAKING_CFG_SEED.as_bytes(), 
             staking_cfg.group_mint.key().as_ref(), 
-            staking_cfg.admin.key().as_ref()
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
         ],
         bump = staking_cfg.bump,
         constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+        constraint = !staking_cfg.pause_flags.stake @ StakingError::StakingPaused,
     )]
     pub staking_cfg: Account<'info, StakingCfg>,
 
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        seeds = [
+            WEIGHT_TABLE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = weight_table.bump,
+    )]
+    pub weight_table: Account<'info, WeightTable>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + StakerPosition::INIT_SPACE,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
     #[account(
         init,
         payer = staker,
@@ -79,6 +117,10 @@ pub struct Stake<'info> {
         constraint = nft_mint.supply == 1 @ StakingError::InvalidNftSupply,
     )]
     pub nft_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// The nft vault, omitted to soft stake the nft in the staker's token account.
+    /// Soft staking needs the freeze authority of the nft mint to be the nft vault authority of this pool,
+    /// so a collection can only be soft staked in the one pool its mints were created for.
     #[account(
         init_if_needed,
         payer = staker,
@@ -86,11 +128,14 @@ pub struct Stake<'info> {
         associated_token::mint = nft_mint,
         associated_token::token_program = token_2022_program,
     )]
-    pub nft_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub nft_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
-    /// CHECK: This is the authority of the nft vault
+    /// CHECK: This is the authority of the nft vault and the freeze authority of soft staked nfts
     #[account(
-        seeds = [NFT_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump,
     )]
     pub nft_vault_authority: AccountInfo<'info>,
@@ -105,80 +150,114 @@ pub struct Stake<'info> {
 }
 
 impl<'info> Stake<'info> {
-    pub fn validate_group_mint(&self) -> Result<()> {
-        let mint_extension_data = get_mint_extension_data::<TokenGroupMember>(&self.nft_mint.to_account_info());
-
-        msg!("mint_extension_data is ok: {:?}", mint_extension_data.is_ok());
-
-        match mint_extension_data {
-            Ok(mint_extension_data) if mint_extension_data.group == self.group_mint.key() => {
-                Ok(())
-            }
-            _=> {
-                Err(StakingError::NftMintNotMatchGroupMint.into())
-            }
-        }
-    }
-
-    pub fn transfer_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
-        CpiContext::new(
+    pub fn transfer_nft(&self, nft_vault: &InterfaceAccount<'info, token_interface::TokenAccount>) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
             self.token_2022_program.to_account_info(),
             TransferChecked {
                 from: self.nft_token_account.to_account_info(),
-                to: self.nft_vault.to_account_info(),
+                to: nft_vault.to_account_info(),
                 authority: self.staker.to_account_info(),
                 mint: self.nft_mint.to_account_info(),
             },
-        )
+        );
+        transfer_checked(cpi_ctx, 1, self.nft_mint.decimals)
     }
-}
 
+    pub fn freeze_nft(&self, nft_vault_authority_bump: u8) -> Result<()> {
+        //CHECK THE POOL CONTROLS THE FREEZE AUTHORITY OF THE NFT
+        require!(
+            self.nft_mint.freeze_authority == Some(self.nft_vault_authority.key()).into(),
+            StakingError::InvalidFreezeAuthority
+        );
 
-pub fn handle_stake(ctx: Context<Stake>) -> Result<()> {
-    let current_time: i64 = Clock::get()?.unix_timestamp;
+        //DELEGATE THE NFT TO THE POOL
+        let approve_ctx = CpiContext::new(
+            self.token_2022_program.to_account_info(),
+            Approve {
+                to: self.nft_token_account.to_account_info(),
+                delegate: self.nft_vault_authority.to_account_info(),
+                authority: self.staker.to_account_info(),
+            },
+        );
+        approve(approve_ctx, 1)?;
+
+        //FREEZE THE NFT IN THE STAKER'S TOKEN ACCOUNT
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[nft_vault_authority_bump],
+        ]];
+        let freeze_ctx = CpiContext::new_with_signer(
+            self.token_2022_program.to_account_info(),
+            FreezeAccount {
+                account: self.nft_token_account.to_account_info(),
+                mint: self.nft_mint.to_account_info(),
+                authority: self.nft_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        freeze_account(freeze_ctx)
+    }
+}
 
-    let staking_cfg = &ctx.accounts.staking_cfg;
-    let StakingCfg{
-        staked_end_time,
-        max_staked_amount,
-        staked_amount,
-        accumulated_reward,
-        latest_reward,
-        latest_reward_time,
-        ..
-    } = **staking_cfg;
+/// Stakes the nft in the nft vault, or soft stakes it by freezing it in the staker's token account
+/// when the nft vault is omitted. The nft vault authority of the pool must be the freeze authority
+/// of the nft mint to soft stake, other nfts fail with InvalidFreezeAuthority.
+pub fn handle_stake(ctx: Context<Stake>, lock_tier: u8) -> Result<()> {
+    let current_time: i64 = ctx.accounts.staking_cfg.reward_period.current_time()?;
 
     //CHECK THE STAKE END TIME IS EXPIRED
-    require_gt!(staked_end_time, current_time, StakingError::StakeEndTimeExpired);
-    require_gte!(max_staked_amount, staked_amount + 1, StakingError::MaxStakedAmountExceeded);
-
-    //CHECK THE GROUP MINT IS THE SAME AS THE NFT'S GROUP MINT
-    ctx.accounts.validate_group_mint()?;
-
-    //TRANSFER THE NFT FROM THE PAYER TO THE NFT VAULT
-    transfer_checked(ctx.accounts.transfer_nft_ctx(), 1, ctx.accounts.nft_mint.decimals)?;
-    
-    //CALCULATE THE REWARD BASED ON THE STAKED TIME
-    let current_reward_from_genesis: u64 = calculate_current_reward_from_genesis(
-        accumulated_reward, 
-        current_time, 
-        latest_reward,
-        latest_reward_time,
+    require_gt!(ctx.accounts.staking_cfg.staked_end_time, current_time, StakingError::StakeEndTimeExpired);
+
+    //CHECKPOINT THE REWARD BEFORE THE STAKED WEIGHT CHANGES
+    let (reward_index, stream_reward_indices) = checkpoint_rewards(
+        &mut ctx.accounts.staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
     )?;
 
-    //INIT THE STAKED RECORD
-    let staked_record = &mut ctx.accounts.staked_record;
-    **staked_record = StakedRecord::init(
-        ctx.bumps.staked_record, 
-        ctx.accounts.staker.key(), 
-        ctx.accounts.nft_mint.key(), 
-        current_time, 
-        current_reward_from_genesis
-    );
-
-    //UPDATE THE STAKING CFG
+    //ADD THE NFT TO THE POOL, THE MAX STAKED AMOUNT CAPS THE TOTAL WEIGHT
+    //SO AN NFT OF TWICE THE BASE WEIGHT USES TWO SLOTS
+    let soft_staked = ctx.accounts.nft_vault.is_none();
+    let staked_record = stake_nft(
+        &mut ctx.accounts.staking_cfg,
+        &ctx.accounts.weight_table,
+        &ctx.accounts.nft_mint,
+        ctx.accounts.staker.key(),
+        ctx.bumps.staked_record,
+        lock_tier,
+        soft_staked,
+        current_time,
+        reward_index,
+        &stream_reward_indices,
+    )?;
+    let weight = staked_record.weight;
     let staking_cfg = &mut ctx.accounts.staking_cfg;
-    staking_cfg.increase_staked_amount(1)?;
-    staking_cfg.increase_total_reward_based_on_staked_time(current_reward_from_genesis)?;
+    staking_cfg.increase_staked_amount(weight)?;
+    staking_cfg.increase_total_reward_based_on_staked_time(staked_record.reward_based_on_staked_time)?;
+    staking_cfg.check_max_staked_amount()?;
+    ctx.accounts.staked_record.set_inner(staked_record);
+
+    match &ctx.accounts.nft_vault {
+        //TRANSFER THE NFT FROM THE PAYER TO THE NFT VAULT
+        Some(nft_vault) => ctx.accounts.transfer_nft(nft_vault)?,
+        //WITHOUT A VAULT THE NFT STAYS FROZEN IN THE STAKER'S TOKEN ACCOUNT
+        None => ctx.accounts.freeze_nft(ctx.bumps.nft_vault_authority)?,
+    }
+
+    //UPDATE THE STAKER POSITION
+    let staking_cfg_key = ctx.accounts.staking_cfg.key();
+    let staker_key = ctx.accounts.staker.key();
+    ctx.accounts.staker_position.add_stake(
+        ctx.bumps.staker_position,
+        staking_cfg_key,
+        staker_key,
+        1,
+        weight,
+        current_time,
+    )?;
+    //CHECK THE STAKER STAYS WITHIN THE WALLET CAP
+    ctx.accounts.staking_cfg.check_max_per_wallet(ctx.accounts.staker_position.staked_count)?;
     Ok(())
-}
\ No newline at end of file
+}
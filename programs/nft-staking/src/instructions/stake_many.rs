@@ -0,0 +1,312 @@
+use {
+    anchor_lang::{
+        prelude::*,
+        system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer},
+    },
+    crate::{
+        state::*,
+        error::*,
+        utils::{
+            checkpoint_rewards,
+            nft_helper::{stake_nft, NFT_ACCOUNTS_LEN},
+        },
+    },
+    anchor_spl::{
+        associated_token::{
+            create_idempotent,
+            get_associated_token_address_with_program_id,
+            AssociatedToken,
+            Create,
+        },
+        token_2022::{
+            transfer_checked,
+            TransferChecked,
+            Token2022,
+        },
+        token_interface::{
+            self,
+            TokenAccount,
+        },
+    },
+};
+
+
+#[derive(Accounts)]
+pub struct StakeMany<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+        constraint = !staking_cfg.pause_flags.stake @ StakingError::StakingPaused,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        seeds = [
+            WEIGHT_TABLE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = weight_table.bump,
+    )]
+    pub weight_table: Account<'info, WeightTable>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + StakerPosition::INIT_SPACE,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
+    /// CHECK: This is the group mint
+    #[account(
+        constraint = group_mint.key() == staking_cfg.group_mint @ StakingError::InvalidGroupMint,
+    )]
+    pub group_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: This is the authority of the nft vault
+    #[account(
+        seeds = [
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.nft_vault_authority_bump,
+    )]
+    pub nft_vault_authority: UncheckedAccount<'info>,
+
+    pub token_2022_program: Program<'info, Token2022>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> StakeMany<'info> {
+    pub fn create_nft_vault(&self, nft_mint: &AccountInfo<'info>, nft_vault: &AccountInfo<'info>) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            Create {
+                payer: self.staker.to_account_info(),
+                associated_token: nft_vault.clone(),
+                authority: self.nft_vault_authority.to_account_info(),
+                mint: nft_mint.clone(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.token_2022_program.to_account_info(),
+            },
+        );
+        create_idempotent(cpi_ctx)
+    }
+
+    /// Creates the staked record the way anchor inits an account, so lamports sent to the
+    /// record address beforehand do not block the stake.
+    pub fn create_staked_record(&self, staked_record: &AccountInfo<'info>, nft_mint: &Pubkey, bump: u8) -> Result<()> {
+        let space = 8 + StakedRecord::INIT_SPACE;
+        let lamports = Rent::get()?.minimum_balance(space);
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            STAKED_RECORD_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            nft_mint.as_ref(),
+            &[bump],
+        ]];
+
+        let current_lamports = staked_record.lamports();
+        if current_lamports == 0 {
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                CreateAccount {
+                    from: self.staker.to_account_info(),
+                    to: staked_record.clone(),
+                },
+                signer_seeds,
+            );
+            return create_account(cpi_ctx, lamports, space as u64, &crate::ID);
+        }
+
+        //TOP UP THE RENT, THEN ALLOCATE AND ASSIGN THE PRE FUNDED ADDRESS
+        let required_lamports = lamports.saturating_sub(current_lamports);
+        if required_lamports > 0 {
+            let cpi_ctx = CpiContext::new(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.staker.to_account_info(),
+                    to: staked_record.clone(),
+                },
+            );
+            transfer(cpi_ctx, required_lamports)?;
+        }
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            Allocate {
+                account_to_allocate: staked_record.clone(),
+            },
+            signer_seeds,
+        );
+        allocate(cpi_ctx, space as u64)?;
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            Assign {
+                account_to_assign: staked_record.clone(),
+            },
+            signer_seeds,
+        );
+        assign(cpi_ctx, &crate::ID)
+    }
+
+    pub fn transfer_nft(
+        &self,
+        nft_mint: &InterfaceAccount<'info, token_interface::Mint>,
+        nft_token_account: &InterfaceAccount<'info, TokenAccount>,
+        nft_vault: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.token_2022_program.to_account_info(),
+            TransferChecked {
+                from: nft_token_account.to_account_info(),
+                to: nft_vault.clone(),
+                authority: self.staker.to_account_info(),
+                mint: nft_mint.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, 1, nft_mint.decimals)
+    }
+}
+
+
+/// Stakes every nft passed in the remaining accounts with the same lock tier,
+/// each as a group of the nft mint, the nft token account, the nft vault and the staked record.
+/// Every nft is moved into its nft vault, soft staking is only supported by `stake`.
+pub fn handle_stake_many<'info>(
+    ctx: Context<'_, '_, 'info, 'info, StakeMany<'info>>,
+    lock_tier: u8,
+) -> Result<()> {
+    let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+    let staking_cfg_key = staking_cfg.key();
+    let staker_key = ctx.accounts.staker.key();
+    let nft_vault_authority_key = ctx.accounts.nft_vault_authority.key();
+    let token_2022_program_key = ctx.accounts.token_2022_program.key();
+
+    //CHECK THE STAKE END TIME IS EXPIRED
+    require_gt!(staking_cfg.staked_end_time, current_time, StakingError::StakeEndTimeExpired);
+
+    //CHECK THE REMAINING ACCOUNTS ARE GROUPS OF NFT ACCOUNTS
+    let remaining_accounts = ctx.remaining_accounts;
+    require!(
+        !remaining_accounts.is_empty() && remaining_accounts.len().is_multiple_of(NFT_ACCOUNTS_LEN),
+        StakingError::InvalidRemainingAccounts
+    );
+
+    //CHECKPOINT THE REWARD ONCE BEFORE THE STAKED WEIGHT CHANGES
+    let (reward_index, stream_reward_indices) = checkpoint_rewards(
+        &mut ctx.accounts.staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
+    )?;
+
+    let mut total_weight: u64 = 0;
+    let mut total_reward_based_on_staked_time: u128 = 0;
+    for accounts in remaining_accounts.chunks(NFT_ACCOUNTS_LEN) {
+        //CHECK THE NFT MINT
+        require_keys_eq!(*accounts[0].owner, token_2022_program_key, StakingError::InvalidNftMint);
+        let nft_mint = InterfaceAccount::<token_interface::Mint>::try_from(&accounts[0])?;
+        require_eq!(nft_mint.supply, 1, StakingError::InvalidNftSupply);
+
+        //CHECK THE NFT TOKEN ACCOUNT
+        let nft_token_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+        require_keys_eq!(nft_token_account.owner, staker_key, StakingError::InvalidNftTokenAccount);
+        require_keys_eq!(nft_token_account.mint, nft_mint.key(), StakingError::InvalidNftTokenAccount);
+        require_eq!(nft_token_account.amount, 1, StakingError::InvalidNftAmount);
+
+        //CHECK THE NFT VAULT
+        let nft_vault_key = get_associated_token_address_with_program_id(
+            &nft_vault_authority_key,
+            &nft_mint.key(),
+            &token_2022_program_key,
+        );
+        require_keys_eq!(accounts[2].key(), nft_vault_key, StakingError::InvalidNftVault);
+
+        //CHECK THE STAKED RECORD
+        let (staked_record_key, staked_record_bump) = Pubkey::find_program_address(
+            &[
+                STAKED_RECORD_SEED.as_bytes(),
+                staking_cfg_key.as_ref(),
+                nft_mint.key().as_ref(),
+            ],
+            &crate::ID,
+        );
+        require_keys_eq!(accounts[3].key(), staked_record_key, StakingError::InvalidStakedRecord);
+
+        //CHECKPOINT THE NFT, ITS WEIGHT IS ADDED TO THE POOL ONCE EVERY NFT IS STAKED
+        let staked_record = stake_nft(
+            &mut ctx.accounts.staking_cfg,
+            &ctx.accounts.weight_table,
+            &nft_mint,
+            staker_key,
+            staked_record_bump,
+            lock_tier,
+            false,
+            current_time,
+            reward_index,
+            &stream_reward_indices,
+        )?;
+
+        //TRANSFER THE NFT FROM THE PAYER TO THE NFT VAULT
+        ctx.accounts.create_nft_vault(&accounts[0], &accounts[2])?;
+        ctx.accounts.transfer_nft(&nft_mint, &nft_token_account, &accounts[2])?;
+
+        //INIT THE STAKED RECORD
+        ctx.accounts.create_staked_record(&accounts[3], &nft_mint.key(), staked_record_bump)?;
+        staked_record.try_serialize(&mut &mut accounts[3].try_borrow_mut_data()?[..])?;
+
+        total_weight = total_weight
+            .checked_add(staked_record.weight)
+            .ok_or(StakingError::ProgramAddError)?;
+        total_reward_based_on_staked_time = total_reward_based_on_staked_time
+            .checked_add(staked_record.reward_based_on_staked_time)
+            .ok_or(StakingError::ProgramAddError)?;
+    }
+
+    //ADD THE NFTS TO THE POOL ONCE, THE MAX STAKED AMOUNT CAPS THE TOTAL WEIGHT
+    let staking_cfg = &mut ctx.accounts.staking_cfg;
+    staking_cfg.increase_staked_amount(total_weight)?;
+    staking_cfg.increase_total_reward_based_on_staked_time(total_reward_based_on_staked_time)?;
+    staking_cfg.check_max_staked_amount()?;
+
+    //UPDATE THE STAKER POSITION ONCE
+    let staked_count = (remaining_accounts.len() / NFT_ACCOUNTS_LEN) as u32;
+    ctx.accounts.staker_position.add_stake(
+        ctx.bumps.staker_position,
+        staking_cfg_key,
+        staker_key,
+        staked_count,
+        total_weight,
+        current_time,
+    )?;
+    //CHECK THE STAKER STAYS WITHIN THE WALLET CAP
+    ctx.accounts.staking_cfg.check_max_per_wallet(ctx.accounts.staker_position.staked_count)?;
+    Ok(())
+}
@@ -2,9 +2,20 @@ use {
     crate::{
         error::*, 
         state::*, 
-        utils::reward_helper::{
-            calculate_reward_for_withdraw, 
-            enable_for_reward
+        utils::{
+            checkpoint_rewards,
+            reward_helper::{
+                calculate_early_unstake_reward,
+                calculate_partial_payout,
+                calculate_redistributed_reward_index,
+                record_owed_reward,
+                calculate_weighted_reward_for_withdraw, 
+                enable_for_reward
+            },
+            reward_stream_helper::{
+                load_reward_stream_accounts,
+                withdraw_stream_rewards,
+            },
         }
     }, 
     anchor_lang::prelude::*, 
@@ -12,8 +23,12 @@ use {
         associated_token::AssociatedToken, 
         token_2022::{
             close_account, 
+            revoke,
+            thaw_account,
             transfer_checked, 
             CloseAccount, 
+            Revoke,
+            ThawAccount,
             Token2022, 
             TransferChecked
         }, 
@@ -34,12 +49,34 @@ pub struct Unstake<'info> {
         seeds = [
             STAKING_CFG_SEED.as_bytes(), 
             staking_cfg.group_mint.key().as_ref(), 
-            staking_cfg.admin.key().as_ref()
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
         ],
         bump = staking_cfg.bump,
+        constraint = !staking_cfg.pause_flags.unstake @ StakingError::UnstakingPaused,
     )]
     pub staking_cfg: Account<'info, StakingCfg>,
 
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = staker_position.bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
     #[account(
         mut,
         seeds = [
@@ -67,13 +104,17 @@ pub struct Unstake<'info> {
     )]
     pub nft_mint: InterfaceAccount<'info, token_interface::Mint>,
 
-    /// CHECK: This is the authority of the nft vault
+    /// CHECK: This is the authority of the nft vault and the freeze authority of soft staked nfts
     #[account(
-        seeds = [NFT_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump = staking_cfg.nft_vault_authority_bump,
     )]
     pub nft_vault_authority: UncheckedAccount<'info>,
 
+    /// The nft vault, omitted when the nft is soft staked
     #[account(
         mut,
         associated_token::authority = nft_vault_authority,
@@ -81,7 +122,7 @@ pub struct Unstake<'info> {
         associated_token::token_program = token_2022_program,
         constraint = nft_vault.amount == 1 @ StakingError::InvalidNftAmount,
     )]
-    pub nft_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub nft_vault: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
     #[account(
         init_if_needed,
@@ -108,10 +149,33 @@ pub struct Unstake<'info> {
 
     /// CHECK: This is the authority of the reward vault
     #[account(
-        seeds = [REWARD_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump = staking_cfg.reward_vault_authority_bump,
     )]
     pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == staking_cfg.treasury @ StakingError::InvalidTreasury,
+    )]
+    pub treasury: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// The reward debt of the staker, required once the reward vault can not pay the whole reward
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + RewardDebt::INIT_SPACE,
+        seeds = [
+            REWARD_DEBT_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub reward_debt: Option<Account<'info, RewardDebt>>,
     
     pub reward_token_program: Interface<'info, TokenInterface>,
 
@@ -134,8 +198,10 @@ impl<'info> Unstake<'info> {
             to: self.reward_token_account.to_account_info(),
             authority: self.reward_vault_authority.to_account_info(),
         };
+        let staking_cfg_key = self.staking_cfg.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
             &[self.staking_cfg.reward_vault_authority_bump],
         ]];
         let cpi_program = self.reward_token_program.to_account_info();
@@ -147,15 +213,39 @@ impl<'info> Unstake<'info> {
         transfer_checked(cpi_ctx, reward_amount, reward_token_decimals)
     }
 
-    pub fn transfer_nft(&self) -> Result<()> {
+    pub fn transfer_penalty(&self, penalty_amount: u64) -> Result<()> {
+        let treasury = self.treasury.as_ref().ok_or(StakingError::InvalidTreasury)?;
+        let transfer_checked_accounts = TransferChecked {
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: treasury.to_account_info(),
+            authority: self.reward_vault_authority.to_account_info(),
+        };
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(),
+            transfer_checked_accounts,
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, penalty_amount, self.reward_mint.decimals)
+    }
+
+    pub fn transfer_nft(&self, nft_vault: &InterfaceAccount<'info, token_interface::TokenAccount>) -> Result<()> {
         let transfer_checked_accounts = TransferChecked   {
-            from: self.nft_vault.to_account_info(),
+            from: nft_vault.to_account_info(),
             mint: self.nft_mint.to_account_info(),
             to: self.nft_token_account.to_account_info(),
             authority: self.nft_vault_authority.to_account_info(),
         };
+        let staking_cfg_key = self.staking_cfg.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
             &[self.staking_cfg.nft_vault_authority_bump],
         ]];
         let cpi_program = self.token_2022_program.to_account_info();
@@ -167,15 +257,17 @@ impl<'info> Unstake<'info> {
         transfer_checked(cpi_ctx, 1, self.nft_mint.decimals)
     }
 
-    pub fn close_nft_vault(&self) -> Result<()> {
+    pub fn close_nft_vault(&self, nft_vault: &InterfaceAccount<'info, token_interface::TokenAccount>) -> Result<()> {
         let close_account_accounts = CloseAccount {
-            account: self.nft_vault.to_account_info(),
+            account: nft_vault.to_account_info(),
             destination: self.staker.to_account_info(),
             authority: self.nft_vault_authority.to_account_info(),
         };
 
+        let staking_cfg_key = self.staking_cfg.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
             &[self.staking_cfg.nft_vault_authority_bump],
         ]];
         
@@ -188,22 +280,55 @@ impl<'info> Unstake<'info> {
         close_account(cpi_ctx)
         
     }
+
+    pub fn thaw_nft(&self) -> Result<()> {
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.nft_vault_authority_bump],
+        ]];
+        let thaw_ctx = CpiContext::new_with_signer(
+            self.token_2022_program.to_account_info(),
+            ThawAccount {
+                account: self.nft_token_account.to_account_info(),
+                mint: self.nft_mint.to_account_info(),
+                authority: self.nft_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        thaw_account(thaw_ctx)?;
+
+        //REVOKE THE POOL DELEGATION
+        let revoke_ctx = CpiContext::new(
+            self.token_2022_program.to_account_info(),
+            Revoke {
+                source: self.nft_token_account.to_account_info(),
+                authority: self.staker.to_account_info(),
+            },
+        );
+        revoke(revoke_ctx)
+    }
     
 }
 
-pub fn handle_unstake(ctx: Context<Unstake>) -> Result<()> {
-    let current_time: i64 = Clock::get()?.unix_timestamp;
-
+pub fn handle_unstake<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Unstake<'info>>,
+) -> Result<()> {
     let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
     let StakingCfg{
         staked_end_time,
         minimum_period,
-        accumulated_reward,
-        latest_reward,
-        latest_reward_time,
+        penalty_policy,
+        penalty_rate,
         ..
     } = **staking_cfg;
 
+    //check if the lock has ended, closing the staking ends every lock
+    let lock_end_time = ctx.accounts.staked_record.lock_end_time.min(staked_end_time);
+    require_gte!(current_time, lock_end_time, StakingError::StakeLocked);
+
     //check if the reward is enabled
     let (is_reward_enabled, staked_end_time) = enable_for_reward(
         current_time, 
@@ -214,35 +339,120 @@ pub fn handle_unstake(ctx: Context<Unstake>) -> Result<()> {
 
     msg!("staked_end_time: {}", staked_end_time);
 
+    //checkpoint the reward before the staked weight changes
+    let (reward_index, stream_reward_indices) = checkpoint_rewards(
+        &mut ctx.accounts.staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
+    )?;
+
+    //calculate the reward
+    let (reward_amount, _) = calculate_weighted_reward_for_withdraw(
+        reward_index, 
+        ctx.accounts.staked_record.reward_based_on_staked_time, 
+        ctx.accounts.staked_record.weight,
+    )?;
 
-    if is_reward_enabled {  
-        //calculate the reward
-        let (reward_amount, _) = calculate_reward_for_withdraw(
-            accumulated_reward, 
-            staked_end_time, 
-            ctx.accounts.staked_record.reward_based_on_staked_time, 
-            latest_reward,
-            latest_reward_time,
+    //an unstake before the minimum period pays the reward by the penalty policy
+    let (reward_payout, reward_penalty) = if is_reward_enabled {
+        (reward_amount, 0)
+    } else {
+        calculate_early_unstake_reward(reward_amount, penalty_policy, penalty_rate)?
+    };
+    //pay what the reward vault holds above the owed reward, the rest is owed to the staker
+    let (reward_paid, reward_owed) = calculate_partial_payout(
+        reward_payout,
+        ctx.accounts.reward_vault.amount,
+        ctx.accounts.staking_cfg.total_owed,
+    );
+    if reward_paid > 0 {
+        //transfer the reward to the reward recipient
+        ctx.accounts.transfer_reward(reward_paid)?;
+    }
+    ctx.accounts.staker_position.add_claimed(reward_paid)?;
+    if reward_owed > 0 {
+        let staking_cfg_key = ctx.accounts.staking_cfg.key();
+        record_owed_reward(
+            &mut ctx.accounts.staking_cfg,
+            staking_cfg_key,
+            ctx.accounts.reward_debt.as_deref_mut(),
+            ctx.bumps.reward_debt,
+            ctx.accounts.staker.key(),
+            reward_owed,
+            current_time,
         )?;
-        let reward_amount_scaled = reward_amount.checked_mul(10_u64.pow(ctx.accounts.reward_mint.decimals as u32)).ok_or(StakingError::ProgramMulError)?;
-        if reward_amount_scaled > ctx.accounts.reward_vault.amount {
-            return Err(StakingError::InsufficientReward.into());
+    }
+    if reward_penalty > 0 && penalty_policy == PenaltyPolicy::Treasury {
+        //transfer the penalty to the treasury, as far as the reward vault holds it above the owed reward
+        let reward_vault_left = ctx.accounts.reward_vault.amount
+            .checked_sub(reward_paid)
+            .ok_or(StakingError::ProgramSubError)?
+            .saturating_sub(ctx.accounts.staking_cfg.total_owed);
+        let penalty_paid = reward_penalty.min(reward_vault_left);
+        if penalty_paid > 0 {
+            ctx.accounts.transfer_penalty(penalty_paid)?;
         }
-        //transfer the reward to the reward recipient
-        ctx.accounts.transfer_reward(reward_amount_scaled)?;
-        
     }
 
-    //transfer the nft to the nft recipient
+    //withdraw the reward streams
+    if !ctx.accounts.staking_cfg.reward_streams.is_empty() {
+        let mut stream_accounts = load_reward_stream_accounts(
+            ctx.remaining_accounts,
+            &ctx.accounts.staking_cfg.reward_streams,
+            &ctx.accounts.reward_vault_authority.key(),
+            &ctx.accounts.staker.key(),
+        )?;
+        if is_reward_enabled {
+            let staking_cfg_key = ctx.accounts.staking_cfg.key();
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+                staking_cfg_key.as_ref(),
+                &[ctx.accounts.staking_cfg.reward_vault_authority_bump],
+            ]];
+            withdraw_stream_rewards(
+                &mut ctx.accounts.staking_cfg.reward_streams,
+                &mut ctx.accounts.staked_record,
+                &stream_reward_indices,
+                &mut stream_accounts,
+                &ctx.accounts.reward_vault_authority.to_account_info(),
+                signer_seeds,
+            )?;
+        }
+
+        //remove the stream checkpoints of the staked record
+        let staked_record = &ctx.accounts.staked_record;
+        for (index, stream) in ctx.accounts.staking_cfg.reward_streams.iter_mut().enumerate() {
+            stream.decrease_total_reward_based_on_staked_time(
+                staked_record.stream_reward_checkpoint(index)
+            )?;
+        }
+    }
+
+    //return the nft to the staker
+    if ctx.accounts.staked_record.soft_staked {
+        ctx.accounts.thaw_nft()?;
+    } else {
+        let nft_vault = ctx.accounts.nft_vault.as_ref().ok_or(StakingError::InvalidNftVault)?;
+        ctx.accounts.transfer_nft(nft_vault)?;
+        //close the nft vault
+        ctx.accounts.close_nft_vault(nft_vault)?;
+    }
 
-    ctx.accounts.transfer_nft()?;
     //update the staking config
     let staking_cfg = &mut ctx.accounts.staking_cfg;
-    staking_cfg.decrease_staked_amount(1)?;
+    staking_cfg.decrease_staked_amount(ctx.accounts.staked_record.weight)?;
     staking_cfg.decrease_total_reward_based_on_staked_time(ctx.accounts.staked_record.reward_based_on_staked_time)?;
-    //close the nft vault
+    if reward_penalty > 0 && penalty_policy == PenaltyPolicy::Redistribute {
+        //share the penalty among the remaining staked weight
+        staking_cfg.accumulated_reward = calculate_redistributed_reward_index(
+            staking_cfg.accumulated_reward,
+            reward_penalty,
+            staking_cfg.staked_amount,
+        )?;
+    }
 
-    ctx.accounts.close_nft_vault()?;
+    //update the staker position
+    ctx.accounts.staker_position.remove_stake(1, ctx.accounts.staked_record.weight)?;
     
     Ok(())
 }
\ No newline at end of file
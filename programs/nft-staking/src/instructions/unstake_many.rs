@@ -0,0 +1,424 @@
+use {
+    crate::{
+        error::*,
+        state::*,
+        utils::{
+            checkpoint_rewards,
+            nft_helper::{load_staked_record, NFT_ACCOUNTS_LEN},
+            reward_helper::{
+                calculate_early_unstake_reward,
+                calculate_partial_payout,
+                calculate_redistributed_reward_index,
+                calculate_weighted_reward_for_withdraw,
+                enable_for_reward,
+                record_owed_reward,
+            },
+            reward_stream_helper::{
+                load_reward_stream_accounts,
+                withdraw_stream_rewards,
+                REWARD_STREAM_ACCOUNTS_LEN,
+            },
+        }
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::{
+        associated_token::{
+            get_associated_token_address_with_program_id,
+            AssociatedToken,
+        },
+        token_2022::{
+            close_account,
+            revoke,
+            thaw_account,
+            transfer_checked,
+            CloseAccount,
+            Revoke,
+            ThawAccount,
+            Token2022,
+            TransferChecked
+        },
+        token_interface::{
+            self,
+            TokenAccount,
+            TokenInterface,
+        }
+    },
+};
+
+#[derive(Accounts)]
+pub struct UnstakeMany<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = !staking_cfg.pause_flags.unstake @ StakingError::UnstakingPaused,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = staker_position.bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
+
+    /// CHECK: This is the authority of the nft vault and the freeze authority of soft staked nfts
+    #[account(
+        seeds = [
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.nft_vault_authority_bump,
+    )]
+    pub nft_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_mint.key() == staking_cfg.reward_token_mint @ StakingError::InvalidRewardMint,
+    )]
+    pub reward_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = treasury.key() == staking_cfg.treasury @ StakingError::InvalidTreasury,
+    )]
+    pub treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The reward debt of the staker, required once the reward vault can not pay the whole reward
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + RewardDebt::INIT_SPACE,
+        seeds = [
+            REWARD_DEBT_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub reward_debt: Option<Account<'info, RewardDebt>>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub token_2022_program: Program<'info, Token2022>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+impl<'info> UnstakeMany<'info> {
+    pub fn transfer_reward(&self, to: AccountInfo<'info>, reward_amount: u64) -> Result<()> {
+        let transfer_checked_accounts = TransferChecked {
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to,
+            authority: self.reward_vault_authority.to_account_info(),
+        };
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(),
+            transfer_checked_accounts,
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, reward_amount, self.reward_mint.decimals)
+    }
+
+    pub fn return_nft(
+        &self,
+        staked_record: &StakedRecord,
+        nft_mint: &InterfaceAccount<'info, token_interface::Mint>,
+        nft_token_account: &InterfaceAccount<'info, TokenAccount>,
+        nft_vault: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            NFT_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.nft_vault_authority_bump],
+        ]];
+        let cpi_program = self.token_2022_program.to_account_info();
+
+        if staked_record.soft_staked {
+            //thaw the nft in the staker's token account and revoke the pool delegation
+            thaw_account(CpiContext::new_with_signer(
+                cpi_program.clone(),
+                ThawAccount {
+                    account: nft_token_account.to_account_info(),
+                    mint: nft_mint.to_account_info(),
+                    authority: self.nft_vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ))?;
+            return revoke(CpiContext::new(
+                cpi_program,
+                Revoke {
+                    source: nft_token_account.to_account_info(),
+                    authority: self.staker.to_account_info(),
+                },
+            ));
+        }
+
+        //check the nft vault
+        let nft_vault_key = get_associated_token_address_with_program_id(
+            &self.nft_vault_authority.key(),
+            &nft_mint.key(),
+            &self.token_2022_program.key(),
+        );
+        require_keys_eq!(nft_vault.key(), nft_vault_key, StakingError::InvalidNftVault);
+
+        //transfer the nft to the staker and close the nft vault
+        transfer_checked(
+            CpiContext::new_with_signer(
+                cpi_program.clone(),
+                TransferChecked {
+                    from: nft_vault.clone(),
+                    mint: nft_mint.to_account_info(),
+                    to: nft_token_account.to_account_info(),
+                    authority: self.nft_vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            1,
+            nft_mint.decimals,
+        )?;
+        close_account(CpiContext::new_with_signer(
+            cpi_program,
+            CloseAccount {
+                account: nft_vault.clone(),
+                destination: self.staker.to_account_info(),
+                authority: self.nft_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        ))
+    }
+}
+
+/// Unstakes every nft passed in the remaining accounts after the reward stream accounts,
+/// each as a group of the nft mint, the nft token account, the nft vault and the staked record.
+pub fn handle_unstake_many<'info>(
+    ctx: Context<'_, '_, 'info, 'info, UnstakeMany<'info>>,
+) -> Result<()> {
+    let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+    let StakingCfg{
+        staked_end_time,
+        minimum_period,
+        penalty_policy,
+        penalty_rate,
+        ..
+    } = **staking_cfg;
+    let staking_cfg_key = staking_cfg.key();
+    let staker_key = ctx.accounts.staker.key();
+
+    //split the remaining accounts into the reward stream accounts and the nft accounts
+    let stream_accounts_len = staking_cfg.reward_streams.len() * REWARD_STREAM_ACCOUNTS_LEN;
+    require_gt!(ctx.remaining_accounts.len(), stream_accounts_len, StakingError::InvalidRemainingAccounts);
+    let (stream_accounts, nft_accounts) = ctx.remaining_accounts.split_at(stream_accounts_len);
+    require_eq!(nft_accounts.len() % NFT_ACCOUNTS_LEN, 0, StakingError::InvalidRemainingAccounts);
+    let mut stream_accounts = load_reward_stream_accounts(
+        stream_accounts,
+        &staking_cfg.reward_streams,
+        &ctx.accounts.reward_vault_authority.key(),
+        &staker_key,
+    )?;
+
+    //checkpoint the reward once before the staked weight changes
+    let (reward_index, stream_reward_indices) = checkpoint_rewards(
+        &mut ctx.accounts.staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
+    )?;
+
+    let reward_signer_seeds: &[&[&[u8]]] = &[&[
+        REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+        staking_cfg_key.as_ref(),
+        &[ctx.accounts.staking_cfg.reward_vault_authority_bump],
+    ]];
+    let mut total_weight: u64 = 0;
+    let mut total_reward_based_on_staked_time: u128 = 0;
+    let mut total_reward_payout: u64 = 0;
+    let mut total_reward_penalty: u64 = 0;
+    for accounts in nft_accounts.chunks(NFT_ACCOUNTS_LEN) {
+        //check the staked record
+        let mut staked_record = load_staked_record(&accounts[3], &staking_cfg_key, &staker_key)?;
+
+        //check the nft mint and the nft token account
+        require_keys_eq!(accounts[0].key(), staked_record.nft_mint, StakingError::InvalidNftMint);
+        let nft_mint = InterfaceAccount::<token_interface::Mint>::try_from(&accounts[0])?;
+        let nft_token_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+        require_keys_eq!(nft_token_account.owner, staker_key, StakingError::InvalidNftTokenAccount);
+        require_keys_eq!(nft_token_account.mint, nft_mint.key(), StakingError::InvalidNftTokenAccount);
+
+        //check if the lock has ended, closing the staking ends every lock
+        let lock_end_time = staked_record.lock_end_time.min(staked_end_time);
+        require_gte!(current_time, lock_end_time, StakingError::StakeLocked);
+
+        //check if the reward is enabled
+        let (is_reward_enabled, _) = enable_for_reward(
+            current_time,
+            staked_end_time,
+            minimum_period,
+            &staked_record
+        )?;
+
+        //calculate the reward, an unstake before the minimum period pays it by the penalty policy
+        let (reward_amount, _) = calculate_weighted_reward_for_withdraw(
+            reward_index,
+            staked_record.reward_based_on_staked_time,
+            staked_record.weight,
+        )?;
+        let (reward_payout, reward_penalty) = if is_reward_enabled {
+            (reward_amount, 0)
+        } else {
+            calculate_early_unstake_reward(reward_amount, penalty_policy, penalty_rate)?
+        };
+        total_reward_payout = total_reward_payout
+            .checked_add(reward_payout)
+            .ok_or(StakingError::ProgramAddError)?;
+        total_reward_penalty = total_reward_penalty
+            .checked_add(reward_penalty)
+            .ok_or(StakingError::ProgramAddError)?;
+
+        //withdraw the reward streams and remove the stream checkpoints of the staked record
+        let staking_cfg = &mut ctx.accounts.staking_cfg;
+        if is_reward_enabled {
+            withdraw_stream_rewards(
+                &mut staking_cfg.reward_streams,
+                &mut staked_record,
+                &stream_reward_indices,
+                &mut stream_accounts,
+                &ctx.accounts.reward_vault_authority.to_account_info(),
+                reward_signer_seeds,
+            )?;
+        }
+        for (index, stream) in staking_cfg.reward_streams.iter_mut().enumerate() {
+            stream.decrease_total_reward_based_on_staked_time(
+                staked_record.stream_reward_checkpoint(index)
+            )?;
+        }
+
+        //return the nft to the staker
+        ctx.accounts.return_nft(&staked_record, &nft_mint, &nft_token_account, &accounts[2])?;
+
+        total_weight = total_weight
+            .checked_add(staked_record.weight)
+            .ok_or(StakingError::ProgramAddError)?;
+        total_reward_based_on_staked_time = total_reward_based_on_staked_time
+            .checked_add(staked_record.reward_based_on_staked_time)
+            .ok_or(StakingError::ProgramAddError)?;
+
+        //close the staked record
+        staked_record.close(ctx.accounts.staker.to_account_info())?;
+    }
+
+    //pay the reward of every nft at once as far as the reward vault holds it above the owed reward, the rest is owed to the staker
+    let (reward_paid, reward_owed) = calculate_partial_payout(
+        total_reward_payout,
+        ctx.accounts.reward_vault.amount,
+        ctx.accounts.staking_cfg.total_owed,
+    );
+    if reward_paid > 0 {
+        //transfer the reward to the reward recipient
+        let reward_token_account = ctx.accounts.reward_token_account.to_account_info();
+        ctx.accounts.transfer_reward(reward_token_account, reward_paid)?;
+    }
+    if reward_owed > 0 {
+        record_owed_reward(
+            &mut ctx.accounts.staking_cfg,
+            staking_cfg_key,
+            ctx.accounts.reward_debt.as_deref_mut(),
+            ctx.bumps.reward_debt,
+            staker_key,
+            reward_owed,
+            current_time,
+        )?;
+    }
+    if total_reward_penalty > 0 && penalty_policy == PenaltyPolicy::Treasury {
+        //transfer the penalty to the treasury, as far as the reward vault holds it above the owed reward
+        let reward_vault_left = ctx.accounts.reward_vault.amount
+            .checked_sub(reward_paid)
+            .ok_or(StakingError::ProgramSubError)?
+            .saturating_sub(ctx.accounts.staking_cfg.total_owed);
+        let penalty_paid = total_reward_penalty.min(reward_vault_left);
+        if penalty_paid > 0 {
+            let treasury = ctx.accounts.treasury.as_ref().ok_or(StakingError::InvalidTreasury)?;
+            ctx.accounts.transfer_reward(treasury.to_account_info(), penalty_paid)?;
+        }
+    }
+
+    //update the staker position once
+    let unstaked_count = (nft_accounts.len() / NFT_ACCOUNTS_LEN) as u32;
+    let staker_position = &mut ctx.accounts.staker_position;
+    staker_position.remove_stake(unstaked_count, total_weight)?;
+    staker_position.add_claimed(reward_paid)?;
+
+    //update the staking config once
+    let staking_cfg = &mut ctx.accounts.staking_cfg;
+    staking_cfg.decrease_staked_amount(total_weight)?;
+    staking_cfg.decrease_total_reward_based_on_staked_time(total_reward_based_on_staked_time)?;
+    if total_reward_penalty > 0 && penalty_policy == PenaltyPolicy::Redistribute {
+        //share the penalty among the remaining staked weight
+        staking_cfg.accumulated_reward = calculate_redistributed_reward_index(
+            staking_cfg.accumulated_reward,
+            total_reward_penalty,
+            staking_cfg.staked_amount,
+        )?;
+    }
+
+    Ok(())
+}
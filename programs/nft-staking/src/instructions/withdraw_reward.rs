@@ -3,9 +3,19 @@ use {
     crate::{
         state::*,
         error::*,
-        utils::reward_helper::{
-            enable_for_reward,
-            calculate_reward_for_withdraw,
+        utils::{
+            reward_helper::{
+                enable_for_reward,
+                calculate_partial_payout,
+                calculate_reward_for_withdraw,
+                record_owed_reward,
+            },
+            reward_stream_helper::{
+                calculate_stream_reward_indices,
+                load_reward_stream_accounts,
+                withdraw_stream_rewards,
+            },
+            token_stake_helper::deposit_token_stake,
         },
     },
     anchor_spl::{
@@ -32,11 +42,33 @@ pub struct WithdrawReward<'info> {
         seeds = [
             STAKING_CFG_SEED.as_bytes(), 
             staking_cfg.group_mint.key().as_ref(), 
-            staking_cfg.admin.key().as_ref()
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
         ],
         bump = staking_cfg.bump,
+        constraint = !staking_cfg.pause_flags.withdraw_reward @ StakingError::WithdrawRewardPaused,
     )]
     pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            STAKER_POSITION_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = staker_position.bump,
+    )]
+    pub staker_position: Account<'info, StakerPosition>,
     
     #[account(
         mut,
@@ -76,11 +108,73 @@ pub struct WithdrawReward<'info> {
 
     /// CHECK: This is the authority of the reward vault
     #[account(
-        seeds = [REWARD_VAULT_AUTHORITY_SEED.as_bytes()],
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
         bump = staking_cfg.reward_vault_authority_bump,
     )]
     pub reward_vault_authority: UncheckedAccount<'info>,
 
+    /// The token pool the reward is compounded into, omitted to pay the reward to the staker
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(),
+            token_stake_cfg.token_mint.as_ref(),
+            token_stake_cfg.creator.as_ref()
+        ],
+        bump = token_stake_cfg.bump,
+        constraint = token_stake_cfg.token_mint == staking_cfg.reward_token_mint @ StakingError::InvalidTokenStakeCfg,
+        constraint = token_stake_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub token_stake_cfg: Option<Account<'info, TokenStakeCfg>>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + TokenStakeRecord::INIT_SPACE,
+        seeds = [
+            TOKEN_STAKE_RECORD_SEED.as_bytes(),
+            token_stake_cfg.as_ref().map(|cfg| cfg.key()).unwrap_or_default().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub token_stake_record: Option<Account<'info, TokenStakeRecord>>,
+
+    #[account(
+        mut,
+        associated_token::authority = token_vault_authority,
+        associated_token::mint = reward_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: This is the authority of the staked token vault of the token pool
+    #[account(
+        seeds = [
+            TOKEN_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.as_ref().map(|cfg| cfg.key()).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub token_vault_authority: Option<UncheckedAccount<'info>>,
+
+    /// The reward debt of the staker, required once the reward vault can not pay the whole reward
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + RewardDebt::INIT_SPACE,
+        seeds = [
+            REWARD_DEBT_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub reward_debt: Option<Account<'info, RewardDebt>>,
+
     pub reward_token_program: Interface<'info, TokenInterface>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -98,8 +192,10 @@ impl<'info> WithdrawReward<'info> {
             to: self.reward_token_account.to_account_info(),
             authority: self.reward_vault_authority.to_account_info(),
         };
+        let staking_cfg_key = self.staking_cfg.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
             &[self.staking_cfg.reward_vault_authority_bump],
         ]];
         let cpi_program = self.reward_token_program.to_account_info();
@@ -110,19 +206,73 @@ impl<'info> WithdrawReward<'info> {
         );
         transfer_checked(cpi_ctx, reward_amount, reward_token_decimals)
     }       
-}
 
-pub fn handle_withdraw_reward(ctx: Context<WithdrawReward>) -> Result<()> {
+    pub fn compound_reward(&mut self, reward_amount: u64, token_stake_record_bump: u8) -> Result<()> {
+        let Some(token_stake_cfg) = self.token_stake_cfg.as_ref() else {
+            return Err(StakingError::InvalidTokenStakeCfg.into());
+        };
+        let current_time: i64 = token_stake_cfg.reward_period.current_time()?;
+
+        //CHECK THE STAKE END TIME IS EXPIRED, the same as a deposit
+        require_gt!(token_stake_cfg.staked_end_time, current_time, StakingError::StakeEndTimeExpired);
+
+        let Some(token_vault) = self.token_vault.as_mut() else {
+            return Err(StakingError::InvalidTokenStakeCfg.into());
+        };
+
+        //transfer the reward to the token vault, a transfer fee is not credited to the staker
+        let vault_amount_before = token_vault.amount;
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(), 
+            TransferChecked {
+                from: self.reward_vault.to_account_info(),
+                mint: self.reward_mint.to_account_info(),
+                to: token_vault.to_account_info(),
+                authority: self.reward_vault_authority.to_account_info(),
+            }, 
+            signer_seeds
+        );
+        transfer_checked(cpi_ctx, reward_amount, self.reward_mint.decimals)?;
+        token_vault.reload()?;
+        let received_amount = token_vault.amount
+            .checked_sub(vault_amount_before)
+            .ok_or(StakingError::ProgramSubError)?;
+
+        //deposit the received reward into the token pool for the staker
+        let (Some(token_stake_cfg), Some(token_stake_record)) = (
+            self.token_stake_cfg.as_mut(),
+            self.token_stake_record.as_mut(),
+        ) else {
+            return Err(StakingError::InvalidTokenStakeCfg.into());
+        };
+        let token_stake_cfg_key = token_stake_cfg.key();
+        deposit_token_stake(
+            token_stake_cfg,
+            token_stake_record,
+            token_stake_record_bump,
+            token_stake_cfg_key,
+            self.staker.key(),
+            received_amount,
+            current_time,
+        )
+    }
+}
 
-    let current_time: i64 = Clock::get()?.unix_timestamp;
+pub fn handle_withdraw_reward<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawReward<'info>>,
+) -> Result<()> {
 
     let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
     let StakingCfg{
         staked_end_time,
         minimum_period,
-        accumulated_reward,
-        latest_reward,
-        latest_reward_time,
         ..
     } = **staking_cfg;
 
@@ -139,30 +289,81 @@ pub fn handle_withdraw_reward(ctx: Context<WithdrawReward>) -> Result<()> {
     }
 
     //calculate the reward
-    let (reward_amount, reward_for_staked_end_time) = calculate_reward_for_withdraw(
-        accumulated_reward, 
+    let (reward_amount, reward_checkpoint) = calculate_reward_for_withdraw(
+        &ctx.accounts.staking_cfg, 
         staked_end_time, 
         ctx.accounts.staked_record.reward_based_on_staked_time, 
-        latest_reward,
-        latest_reward_time,
+        ctx.accounts.staked_record.weight,
+        &ctx.accounts.emission_schedule.segments,
     )?;
 
-    //check if the reward vault is enough
-    let reward_amount_scaled = reward_amount.checked_mul(10_u64.pow(ctx.accounts.reward_mint.decimals as u32)).ok_or(StakingError::ProgramMulError)?;
-    if reward_amount_scaled > ctx.accounts.reward_vault.amount {
-        return Err(StakingError::InsufficientReward.into());
+    //pay what the reward vault holds above the owed reward, the rest is owed to the staker
+    let (reward_paid, reward_owed) = calculate_partial_payout(
+        reward_amount,
+        ctx.accounts.reward_vault.amount,
+        ctx.accounts.staking_cfg.total_owed,
+    );
+    if ctx.accounts.token_stake_cfg.is_none() {
+        //transfer the reward to the reward recipient
+        ctx.accounts.transfer_reward(reward_paid)?;
+    } else if reward_paid > 0 {
+        //compound the reward into the token pool
+        let token_stake_record_bump = ctx.bumps.token_stake_record.ok_or(StakingError::InvalidTokenStakeCfg)?;
+        ctx.accounts.compound_reward(reward_paid, token_stake_record_bump)?;
+    }
+    ctx.accounts.staker_position.add_claimed(reward_paid)?;
+    if reward_owed > 0 {
+        let staking_cfg_key = ctx.accounts.staking_cfg.key();
+        record_owed_reward(
+            &mut ctx.accounts.staking_cfg,
+            staking_cfg_key,
+            ctx.accounts.reward_debt.as_deref_mut(),
+            ctx.bumps.reward_debt,
+            ctx.accounts.staker.key(),
+            reward_owed,
+            current_time,
+        )?;
     }
-    //transfer the reward to the reward recipient
-    ctx.accounts.transfer_reward(reward_amount_scaled)?;
     //update the staked record
     let staked_record = &mut ctx.accounts.staked_record;
+    let total_reward_paid = reward_checkpoint
+        .checked_sub(staked_record.reward_based_on_staked_time)
+        .ok_or(StakingError::ProgramSubError)?;
     staked_record.refresh_for_withdraw(
-        reward_for_staked_end_time, 
+        reward_checkpoint, 
         staked_end_time)?;
 
     //increase the total reward based on staked time
     let staking_cfg = &mut ctx.accounts.staking_cfg;
-    staking_cfg.increase_total_reward_based_on_staked_time(reward_amount)?;
+    staking_cfg.increase_total_reward_based_on_staked_time(total_reward_paid)?;
+
+    //withdraw the reward streams
+    if !ctx.accounts.staking_cfg.reward_streams.is_empty() {
+        let mut stream_accounts = load_reward_stream_accounts(
+            ctx.remaining_accounts,
+            &ctx.accounts.staking_cfg.reward_streams,
+            &ctx.accounts.reward_vault_authority.key(),
+            &ctx.accounts.staker.key(),
+        )?;
+        let stream_reward_indices = calculate_stream_reward_indices(
+            &ctx.accounts.staking_cfg,
+            current_time,
+        )?;
+        let staking_cfg_key = ctx.accounts.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[ctx.accounts.staking_cfg.reward_vault_authority_bump],
+        ]];
+        withdraw_stream_rewards(
+            &mut ctx.accounts.staking_cfg.reward_streams,
+            &mut ctx.accounts.staked_record,
+            &stream_reward_indices,
+            &mut stream_accounts,
+            &ctx.accounts.reward_vault_authority.to_account_info(),
+            signer_seeds,
+        )?;
+    }
     Ok(())
         
 }
@@ -0,0 +1,136 @@
+use {
+    anchor_lang::prelude::*,
+    crate::{
+        state::*,
+        error::*,
+        utils::reward_helper::calculate_scheduled_reward_liability,
+    },
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint,
+            TokenAccount,
+            TokenInterface
+        },
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct WithdrawSurplus<'info> {
+    /// The treasurer, the admin when no treasurer is set
+    #[account(mut)]
+    pub treasurer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            STAKING_CFG_SEED.as_bytes(),
+            staking_cfg.group_mint.key().as_ref(),
+            staking_cfg.creator.as_ref(),
+            staking_cfg.pool_id.to_le_bytes().as_ref()
+        ],
+        bump = staking_cfg.bump,
+        constraint = staking_cfg.is_treasurer(&treasurer.key()) @ StakingError::Unauthorized,
+        constraint = staking_cfg.is_active @ StakingError::StakingNotActive,
+    )]
+    pub staking_cfg: Account<'info, StakingCfg>,
+
+    #[account(
+        seeds = [
+            EMISSION_SCHEDULE_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = emission_schedule.bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_mint.key() == staking_cfg.reward_token_mint @ StakingError::InvalidRewardMint,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg.key().as_ref(),
+        ],
+        bump = staking_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = treasurer,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> WithdrawSurplus<'info> {
+    pub fn transfer_reward(&self, amount: u64) -> Result<()> {
+        let reward_token_decimals = self.reward_mint.decimals;
+        let staking_cfg_key = self.staking_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            staking_cfg_key.as_ref(),
+            &[self.staking_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(),
+            TransferChecked {
+                from: self.reward_vault.to_account_info(),
+                to: self.reward_token_account.to_account_info(),
+                authority: self.reward_vault_authority.to_account_info(),
+                mint: self.reward_mint.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(
+            cpi_ctx,
+            amount,
+            reward_token_decimals,
+        )
+    }
+}
+
+/// Sends the reward vault balance above the pool liability to the treasurer.
+/// The liability is the reward accrued so far, the scheduled reward of every slot up to
+/// the end time and the reward owed to the stakers, so the surplus left by a shorter end time
+/// or a lower reward can be withdrawn without touching what the stakers are promised.
+pub fn handle_withdraw_surplus(ctx: Context<WithdrawSurplus>) -> Result<()> {
+    let staking_cfg = &ctx.accounts.staking_cfg;
+    let current_time: i64 = staking_cfg.reward_period.current_time()?;
+
+    // the reward the pool still has to pay up to the end time and the reward owed to the stakers
+    let total_reward_needed = calculate_scheduled_reward_liability(
+        staking_cfg,
+        current_time,
+        &ctx.accounts.emission_schedule.segments,
+    )?
+    .checked_add(staking_cfg.total_owed)
+    .ok_or(StakingError::ProgramAddError)?;
+
+    let surplus = ctx.accounts.reward_vault.amount.saturating_sub(total_reward_needed);
+    if surplus > 0 {
+        ctx.accounts.transfer_reward(surplus)?;
+    }
+    Ok(())
+}
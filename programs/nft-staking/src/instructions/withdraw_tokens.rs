@@ -0,0 +1,190 @@
+use {
+    anchor_lang::prelude::*,
+    crate::{
+        state::*,
+        error::*,
+        utils::token_stake_helper::withdraw_token_stake,
+    },
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{
+            Mint, 
+            TokenAccount,
+            TokenInterface},
+        token_2022::{
+            TransferChecked,
+            transfer_checked,
+        },
+    },
+};
+
+#[derive(Accounts)]
+pub struct WithdrawTokens<'info> {
+    #[account(mut)]
+    pub staker: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_CFG_SEED.as_bytes(), 
+            token_stake_cfg.token_mint.as_ref(), 
+            token_stake_cfg.creator.as_ref()
+        ],
+        bump = token_stake_cfg.bump,
+    )]
+    pub token_stake_cfg: Account<'info, TokenStakeCfg>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_STAKE_RECORD_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump = token_stake_record.bump,
+        has_one = staker,
+    )]
+    pub token_stake_record: Account<'info, TokenStakeRecord>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = token_mint.key() == token_stake_cfg.token_mint @ StakingError::InvalidTokenStakeCfg,
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = token_vault_authority,
+        associated_token::mint = token_mint,
+        associated_token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the staked token vault
+    #[account(
+        seeds = [
+            TOKEN_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+        ],
+        bump = token_stake_cfg.token_vault_authority_bump,
+    )]
+    pub token_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mint::token_program = reward_token_program,
+        constraint = reward_token_mint.key() == token_stake_cfg.reward_token_mint @ StakingError::InvalidTokenStakeCfg,
+    )]
+    pub reward_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = reward_vault_authority,
+        associated_token::mint = reward_token_mint,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the authority of the reward vault
+    #[account(
+        seeds = [
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg.key().as_ref(),
+        ],
+        bump = token_stake_cfg.reward_vault_authority_bump,
+    )]
+    pub reward_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = token_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_token_mint,
+        associated_token::authority = staker,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawTokens<'info> {
+    pub fn transfer_reward(&self, reward_amount: u64) -> Result<()> {
+        let token_stake_cfg_key = self.token_stake_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg_key.as_ref(),
+            &[self.token_stake_cfg.reward_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.reward_token_program.to_account_info(), 
+            TransferChecked {
+                from: self.reward_vault.to_account_info(),
+                mint: self.reward_token_mint.to_account_info(),
+                to: self.reward_token_account.to_account_info(),
+                authority: self.reward_vault_authority.to_account_info(),
+            }, 
+            signer_seeds
+        );
+        transfer_checked(cpi_ctx, reward_amount, self.reward_token_mint.decimals)
+    }
+
+    pub fn transfer_tokens(&self, amount: u64) -> Result<()> {
+        let token_stake_cfg_key = self.token_stake_cfg.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            TOKEN_VAULT_AUTHORITY_SEED.as_bytes(),
+            token_stake_cfg_key.as_ref(),
+            &[self.token_stake_cfg.token_vault_authority_bump],
+        ]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(), 
+            TransferChecked {
+                from: self.token_vault.to_account_info(),
+                mint: self.token_mint.to_account_info(),
+                to: self.token_account.to_account_info(),
+                authority: self.token_vault_authority.to_account_info(),
+            }, 
+            signer_seeds
+        );
+        transfer_checked(cpi_ctx, amount, self.token_mint.decimals)
+    }
+}
+
+/// Withdraws `amount` of the staked tokens together with the reward accrued by the token stake record.
+/// The staked tokens are always returned, even when the reward vault is short.
+pub fn handle_withdraw_tokens(ctx: Context<WithdrawTokens>, amount: u64) -> Result<()> {
+    let current_time: i64 = ctx.accounts.token_stake_cfg.reward_period.current_time()?;
+
+    //a short reward vault pays what it holds, the rest stays pending on the record
+    let reward_amount = withdraw_token_stake(
+        &mut ctx.accounts.token_stake_cfg,
+        &mut ctx.accounts.token_stake_record,
+        amount,
+        ctx.accounts.reward_vault.amount,
+        current_time,
+    )?;
+
+    //transfer the reward to the staker
+    if reward_amount > 0 {
+        ctx.accounts.transfer_reward(reward_amount)?;
+    }
+
+    //transfer the staked tokens to the staker
+    if amount > 0 {
+        ctx.accounts.transfer_tokens(amount)?;
+    }
+    Ok(())
+}
@@ -2,7 +2,7 @@ mod error;
 mod instructions;
 mod state;
 mod utils;
-use {anchor_lang::prelude::*, instructions::*};
+use {anchor_lang::prelude::*, instructions::*, state::{FundMode, LockTier, PauseFlags, PenaltyPolicy, PoolRoles, RateSegment, RewardMode, RewardPeriod, WeightEntry}};
 
 declare_id!("8ketmx972udmAo4xgLWcVizKF4CXz5jjejbEWv2pVUaJ");
 
@@ -10,45 +10,184 @@ declare_id!("8ketmx972udmAo4xgLWcVizKF4CXz5jjejbEWv2pVUaJ");
 pub mod nft_staking {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn init_staking(
         ctx: Context<InitStaking>,
+        pool_id: u64,
         reward: u64,
+        reward_period: RewardPeriod,
+        reward_mode: RewardMode,
         staked_start_time: i64,
         staked_end_time: i64,
         minimum_period: i64,
         max_staked_amount: u64,
+        max_per_wallet: Option<u64>,
+        segments: Vec<RateSegment>,
     ) -> Result<()> {
         handle_init_staking(
             ctx,
+            pool_id,
             reward,
+            reward_period,
+            reward_mode,
             staked_start_time,
             staked_end_time,
             minimum_period,
             max_staked_amount,
+            max_per_wallet,
+            segments,
         )
     }
 
-    pub fn stake(ctx: Context<Stake>) -> Result<()> {
-        handle_stake(ctx)
+    pub fn stake(ctx: Context<Stake>, lock_tier: u8) -> Result<()> {
+        handle_stake(ctx, lock_tier)
     }
 
-    pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
+    pub fn stake_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StakeMany<'info>>,
+        lock_tier: u8,
+    ) -> Result<()> {
+        handle_stake_many(ctx, lock_tier)
+    }
+
+    pub fn unstake<'info>(ctx: Context<'_, '_, 'info, 'info, Unstake<'info>>) -> Result<()> {
         handle_unstake(ctx)
     }
 
-    pub fn withdraw_reward(ctx: Context<WithdrawReward>) -> Result<()> {
+    pub fn unstake_many<'info>(ctx: Context<'_, '_, 'info, 'info, UnstakeMany<'info>>) -> Result<()> {
+        handle_unstake_many(ctx)
+    }
+
+    pub fn emergency_unstake(ctx: Context<EmergencyUnstake>) -> Result<()> {
+        handle_emergency_unstake(ctx)
+    }
+
+    pub fn withdraw_reward<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawReward<'info>>,
+    ) -> Result<()> {
         handle_withdraw_reward(ctx)
     }
 
+    pub fn claim_all<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimAll<'info>>) -> Result<()> {
+        handle_claim_all(ctx)
+    }
+
+    pub fn claim_owed(ctx: Context<ClaimOwed>) -> Result<()> {
+        handle_claim_owed(ctx)
+    }
+
     pub fn config_staking(
         ctx: Context<ConfigStaking>,
         reward: Option<u64>,
         staked_end_time: Option<i64>,
+        minimum_period: Option<i64>,
+        max_staked_amount: Option<u64>,
+        max_per_wallet: Option<u64>,
     ) -> Result<()> {
-        handle_config_staking(ctx, reward, staked_end_time)
+        handle_config_staking(
+            ctx,
+            reward,
+            staked_end_time,
+            minimum_period,
+            max_staked_amount,
+            max_per_wallet,
+        )
+    }
+
+    pub fn withdraw_surplus(ctx: Context<WithdrawSurplus>) -> Result<()> {
+        handle_withdraw_surplus(ctx)
     }
 
-    pub fn close_staking(ctx: Context<CloseStaking>) -> Result<()> {
+    pub fn close_staking<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseStaking<'info>>,
+    ) -> Result<()> {
         handle_close_staking(ctx)
     }
+
+    pub fn fund_rewards(
+        ctx: Context<FundRewards>,
+        amount: u64,
+        fund_mode: FundMode,
+    ) -> Result<()> {
+        handle_fund_rewards(ctx, amount, fund_mode)
+    }
+
+    pub fn add_reward_stream(
+        ctx: Context<AddRewardStream>,
+        reward: u64,
+        staked_end_time: i64,
+    ) -> Result<()> {
+        handle_add_reward_stream(ctx, reward, staked_end_time)
+    }
+
+    pub fn set_weight_table(
+        ctx: Context<SetWeightTable>,
+        metadata_key: String,
+        default_weight: u64,
+        entries: Vec<WeightEntry>,
+    ) -> Result<()> {
+        handle_set_weight_table(ctx, metadata_key, default_weight, entries)
+    }
+
+    pub fn set_lock_tiers(ctx: Context<SetLockTiers>, lock_tiers: Vec<LockTier>) -> Result<()> {
+        handle_set_lock_tiers(ctx, lock_tiers)
+    }
+
+    pub fn set_penalty_policy(
+        ctx: Context<SetPenaltyPolicy>,
+        penalty_policy: PenaltyPolicy,
+        penalty_rate: u16,
+    ) -> Result<()> {
+        handle_set_penalty_policy(ctx, penalty_policy, penalty_rate)
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, pending_admin: Pubkey) -> Result<()> {
+        handle_propose_admin(ctx, pending_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        handle_accept_admin(ctx)
+    }
+
+    pub fn set_roles(ctx: Context<SetRoles>, roles: PoolRoles) -> Result<()> {
+        handle_set_roles(ctx, roles)
+    }
+
+    pub fn pause_staking(ctx: Context<PauseStaking>, pause_flags: PauseFlags) -> Result<()> {
+        handle_pause_staking(ctx, pause_flags)
+    }
+
+    pub fn resume_staking(ctx: Context<ResumeStaking>, pause_flags: PauseFlags) -> Result<()> {
+        handle_resume_staking(ctx, pause_flags)
+    }
+
+    pub fn init_token_staking(
+        ctx: Context<InitTokenStaking>,
+        reward: u64,
+        reward_period: RewardPeriod,
+        staked_start_time: i64,
+        staked_end_time: i64,
+    ) -> Result<()> {
+        handle_init_token_staking(ctx, reward, reward_period, staked_start_time, staked_end_time)
+    }
+
+    pub fn deposit_tokens(ctx: Context<DepositTokens>, amount: u64) -> Result<()> {
+        handle_deposit_tokens(ctx, amount)
+    }
+
+    pub fn withdraw_tokens(ctx: Context<WithdrawTokens>, amount: u64) -> Result<()> {
+        handle_withdraw_tokens(ctx, amount)
+    }
+
+    pub fn close_token_staking(ctx: Context<CloseTokenStaking>) -> Result<()> {
+        handle_close_token_staking(ctx)
+    }
+
+    pub fn propose_token_stake_admin(ctx: Context<ProposeTokenStakeAdmin>, pending_admin: Pubkey) -> Result<()> {
+        handle_propose_token_stake_admin(ctx, pending_admin)
+    }
+
+    pub fn accept_token_stake_admin(ctx: Context<AcceptTokenStakeAdmin>) -> Result<()> {
+        handle_accept_token_stake_admin(ctx)
+    }
 }
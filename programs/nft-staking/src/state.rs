@@ -1,6 +1,5 @@
 use {
     crate::error::*,
-    anchor_attribute_account::account,
     anchor_lang::{prelude::*, InitSpace},
     solana_program::pubkey::Pubkey,
 };
@@ -10,6 +9,69 @@ pub const NFT_VAULT_AUTHORITY_SEED: &str = "nft_vault_authority";
 pub const STAKING_CFG_SEED: &str = "staking_cfg";
 pub const CFG_UPDATE_RECORD_SEED: &str = "cfg_update_record";
 pub const STAKED_RECORD_SEED: &str = "staked_record";
+pub const EMISSION_SCHEDULE_SEED: &str = "emission_schedule";
+pub const SPONSOR_RECORD_SEED: &str = "sponsor_record";
+pub const WEIGHT_TABLE_SEED: &str = "weight_table";
+pub const STAKER_POSITION_SEED: &str = "staker_position";
+pub const TOKEN_STAKE_CFG_SEED: &str = "token_stake_cfg";
+pub const TOKEN_STAKE_RECORD_SEED: &str = "token_stake_record";
+pub const TOKEN_VAULT_AUTHORITY_SEED: &str = "token_vault_authority";
+pub const REWARD_DEBT_SEED: &str = "reward_debt";
+
+/// The maximum number of rate segments in an emission schedule
+pub const MAX_RATE_SEGMENTS: usize = 16;
+
+/// The maximum number of additional reward streams per staking pool
+pub const MAX_REWARD_STREAMS: usize = 4;
+
+/// The maximum number of entries in a weight table
+pub const MAX_WEIGHT_ENTRIES: usize = 16;
+
+/// The maximum length of a metadata key or value in a weight table
+pub const MAX_METADATA_LEN: usize = 32;
+
+/// The maximum number of lock tiers per staking pool
+pub const MAX_LOCK_TIERS: usize = 4;
+
+/// The penalty rate withholding the whole early reward, in basis points
+pub const MAX_PENALTY_RATE: u16 = 10_000;
+
+/// The weight of a staked nft without a rarity bonus, in basis points
+pub const BASE_WEIGHT: u64 = 10_000;
+
+/// The scale of the reward index, one base unit of reward equals 1e18 index units
+pub const REWARD_INDEX_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// The unit the reward rate is paid in. Epoch based pools measure every
+/// pool time (start, end, minimum period, staked at) in epochs instead of unix seconds.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum RewardPeriod {
+    Second,
+    Hour,
+    Day,
+    Epoch,
+}
+
+/// How the reward rate is split. Per nft pools pay every staked nft the rate, shared
+/// pools split the rate between the staked weight so the whole emission is paid out.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum RewardMode {
+    PerNft,
+    Shared,
+}
+
+/// What happens to the reward of an nft unstaked before the minimum period
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum PenaltyPolicy {
+    /// The whole reward is forfeited and stays in the reward vault
+    Forfeit,
+    /// The reward is paid minus the penalty, the penalty stays in the reward vault
+    Payout,
+    /// The reward is paid minus the penalty, the penalty is sent to the treasury
+    Treasury,
+    /// The reward is paid minus the penalty, the penalty is shared by the remaining stakers
+    Redistribute,
+}
 
 #[account]
 #[derive(InitSpace)]
@@ -18,8 +80,20 @@ pub struct StakingCfg {
     pub bump: u8,
     /// The is active
     pub is_active: bool,
+    /// The actions paused by the pauser
+    pub pause_flags: PauseFlags,
+    /// The time the pool was paused, or the accrual frozen
+    pub paused_at: i64,
     /// administrator
     pub admin: Pubkey,
+    /// The administrator proposed by the admin, default when none is pending
+    pub pending_admin: Pubkey,
+    /// The roles delegated by the admin
+    pub roles: PoolRoles,
+    /// The creator of the pool, it stays the same when the admin changes
+    pub creator: Pubkey,
+    /// The pool id, the staking cfg is derived from the group mint, the creator and the pool id
+    pub pool_id: u64,
     /// The group mint
     pub group_mint: Pubkey,
     /// The reward token mint
@@ -28,26 +102,109 @@ pub struct StakingCfg {
     pub reward_vault_authority_bump: u8,
     /// The staked token vault authority bump
     pub nft_vault_authority_bump: u8,
-    /// The maximum staked amount
+    /// The maximum staked amount in nft slots of the base weight, it caps the total staked weight
+    /// so an nft of twice the base weight uses two slots
     pub max_staked_amount: u64,
+    /// The maximum number of nfts staked per wallet, zero for no cap
+    pub max_per_wallet: u64,
     /// The staked start time
     pub staked_start_time: i64,
     /// The staked end time
     pub staked_end_time: i64,
-    /// The latest reward
+    /// The latest reward in base units per reward period per staked nft, or for the whole pool in shared mode
     pub latest_reward: u64,
+    /// The reward period unit
+    pub reward_period: RewardPeriod,
+    /// The reward mode
+    pub reward_mode: RewardMode,
     /// The latest reward time
     pub latest_reward_time: i64,
-    /// The accumulated reward
-    pub accumulated_reward: u64,
-    /// The total staked reward based on staked time
-    pub total_reward_based_on_staked_time: u64,
-    /// The staked amount
+    /// The accumulated reward index, scaled by REWARD_INDEX_PRECISION
+    pub accumulated_reward: u128,
+    /// The total staked reward based on staked time, scaled by REWARD_INDEX_PRECISION
+    pub total_reward_based_on_staked_time: u128,
+    /// The total weight of the staked nfts, in basis points
     pub staked_amount: u64,
     /// The minimum stake period to be eligible for reward
     pub minimum_period: i64,
+    /// The penalty policy of an unstake before the minimum period
+    pub penalty_policy: PenaltyPolicy,
+    /// The part of the early reward withheld as penalty, in basis points
+    pub penalty_rate: u16,
+    /// The treasury token account receiving the penalty
+    pub treasury: Pubkey,
+    /// The reward owed to the stakers the reward vault could not pay, in base units
+    pub total_owed: u64,
     /// The updated times
     pub updated_times: u32,
+    /// The additional reward streams paid next to the reward token
+    #[max_len(MAX_REWARD_STREAMS)]
+    pub reward_streams: Vec<RewardStream>,
+    /// The lock tiers stakers choose from when staking
+    #[max_len(MAX_LOCK_TIERS)]
+    pub lock_tiers: Vec<LockTier>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug, Default)]
+pub struct PauseFlags {
+    /// Blocks staking nfts
+    pub stake: bool,
+    /// Blocks withdrawing the reward
+    pub withdraw_reward: bool,
+    /// Blocks unstaking nfts
+    pub unstake: bool,
+    /// Freezes the reward accrual while any action is paused, the paused time is not paid
+    pub freeze_accrual: bool,
+}
+
+impl PauseFlags {
+    pub fn is_paused(&self) -> bool {
+        self.stake || self.withdraw_reward || self.unstake
+    }
+
+    pub fn is_accrual_frozen(&self) -> bool {
+        self.freeze_accrual && self.is_paused()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug, Default)]
+pub struct PoolRoles {
+    /// The operator, may change the reward within the operator bounds
+    pub operator: Pubkey,
+    /// The treasurer, alone withdraws the surplus reward, the admin when unset
+    pub treasurer: Pubkey,
+    /// The pauser, may only pause the pool
+    pub pauser: Pubkey,
+    /// The lowest reward the operator may set
+    pub min_operator_reward: u64,
+    /// The highest reward the operator may set
+    pub max_operator_reward: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct LockTier {
+    /// The period the staked nft can not be unstaked for
+    pub lock_period: i64,
+    /// The reward multiplier, in basis points
+    pub multiplier: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct RewardStream {
+    /// The reward token mint of the stream
+    pub reward_token_mint: Pubkey,
+    /// The token program of the stream reward token mint
+    pub token_program: Pubkey,
+    /// The stream end time
+    pub staked_end_time: i64,
+    /// The reward in base units per reward period per staked nft, or for the whole pool in shared mode
+    pub latest_reward: u64,
+    /// The latest reward time
+    pub latest_reward_time: i64,
+    /// The accumulated reward index, scaled by REWARD_INDEX_PRECISION
+    pub accumulated_reward: u128,
+    /// The total staked reward based on staked time, scaled by REWARD_INDEX_PRECISION
+    pub total_reward_based_on_staked_time: u128,
 }
 
 #[account]
@@ -63,6 +220,112 @@ pub struct CfgUpdateRecord {
     pub updated_at: i64,
 }
 
+/// How a sponsor deposit is turned into rewards
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FundMode {
+    /// Extend the staked end time at the rate in effect at the end time
+    ExtendEndTime,
+    /// Raise the reward for the remaining staking period
+    RaiseReward,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct RateSegment {
+    /// The time the segment rate starts to apply
+    pub start_time: i64,
+    /// The reward in base units per reward period per staked nft
+    pub reward: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct EmissionSchedule {
+    /// The bump seed for the emission schedule account
+    pub bump: u8,
+    /// The staking cfg
+    pub staking_cfg: Pubkey,
+    /// The future rate segments, sorted by start time
+    #[max_len(MAX_RATE_SEGMENTS)]
+    pub segments: Vec<RateSegment>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SponsorRecord {
+    /// The bump seed for the sponsor record account
+    pub bump: u8,
+    /// The staking cfg
+    pub staking_cfg: Pubkey,
+    /// The sponsor
+    pub sponsor: Pubkey,
+    /// The total reward funded by the sponsor
+    pub total_funded: u64,
+    /// The funded times
+    pub funded_times: u32,
+    /// The last funded at
+    pub last_funded_at: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct StakerPosition {
+    /// The bump seed for the staker position account
+    pub bump: u8,
+    /// The staking cfg
+    pub staking_cfg: Pubkey,
+    /// The staker
+    pub staker: Pubkey,
+    /// The number of nfts staked by the staker
+    pub staked_count: u32,
+    /// The total weight of the nfts staked by the staker, in basis points
+    pub staked_weight: u64,
+    /// The reward claimed by the staker over the lifetime of the pool
+    pub total_claimed: u64,
+    /// The first staked at
+    pub first_staked_at: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RewardDebt {
+    /// The bump seed for the reward debt account
+    pub bump: u8,
+    /// The staking cfg
+    pub staking_cfg: Pubkey,
+    /// The staker
+    pub staker: Pubkey,
+    /// The reward owed to the staker, in base units
+    pub owed: u64,
+    /// The updated at
+    pub updated_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace, Debug)]
+pub struct WeightEntry {
+    /// The metadata value
+    #[max_len(MAX_METADATA_LEN)]
+    pub value: String,
+    /// The weight of nfts with the metadata value, in basis points
+    pub weight: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct WeightTable {
+    /// The bump seed for the weight table account
+    pub bump: u8,
+    /// The staking cfg
+    pub staking_cfg: Pubkey,
+    /// The additional metadata key the weight is read from, empty for equal weights
+    #[max_len(MAX_METADATA_LEN)]
+    pub metadata_key: String,
+    /// The weight of nfts without a matching entry, in basis points
+    pub default_weight: u64,
+    /// The weights keyed on the metadata value
+    #[max_len(MAX_WEIGHT_ENTRIES)]
+    pub entries: Vec<WeightEntry>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct StakedRecord {
@@ -76,44 +339,320 @@ pub struct StakedRecord {
     pub staked_at: i64,
     /// The withdraw at
     pub withdraw_at: i64,
-    /// The reward based on the staked time
-    pub reward_based_on_staked_time: u64,
+    /// The reward weight of the staked nft with the lock multiplier applied, in basis points
+    pub weight: u64,
+    /// The lock tier chosen when staking
+    pub lock_tier: u8,
+    /// The time the staked nft can be unstaked from
+    pub lock_end_time: i64,
+    /// Whether the nft is frozen in the staker's token account instead of held in the nft vault
+    pub soft_staked: bool,
+    /// The weighted reward index checkpoint, scaled by REWARD_INDEX_PRECISION
+    pub reward_based_on_staked_time: u128,
+    /// The weighted reward index checkpoints of the reward streams, scaled by REWARD_INDEX_PRECISION
+    #[max_len(MAX_REWARD_STREAMS)]
+    pub stream_reward_based_on_staked_time: Vec<u128>,
+}
+
+/// A fungible token staking pool. The rate is shared by the staked tokens,
+/// every staked base unit weighs one basis point.
+#[account]
+#[derive(InitSpace)]
+pub struct TokenStakeCfg {
+    /// The bump seed for the token stake cfg account
+    pub bump: u8,
+    /// The is active
+    pub is_active: bool,
+    /// administrator
+    pub admin: Pubkey,
+    /// The administrator proposed by the admin, default when none is pending
+    pub pending_admin: Pubkey,
+    /// The creator of the pool, the token stake cfg is derived from the token mint and the creator
+    pub creator: Pubkey,
+    /// The staked token mint
+    pub token_mint: Pubkey,
+    /// The reward token mint
+    pub reward_token_mint: Pubkey,
+    /// The reward token vault authority bump
+    pub reward_vault_authority_bump: u8,
+    /// The staked token vault authority bump
+    pub token_vault_authority_bump: u8,
+    /// The staked start time
+    pub staked_start_time: i64,
+    /// The staked end time
+    pub staked_end_time: i64,
+    /// The reward in base units per reward period for the whole pool
+    pub latest_reward: u64,
+    /// The reward period unit
+    pub reward_period: RewardPeriod,
+    /// The latest reward time
+    pub latest_reward_time: i64,
+    /// The accumulated reward index, scaled by REWARD_INDEX_PRECISION
+    pub accumulated_reward: u128,
+    /// The total staked token amount, in base units
+    pub staked_amount: u64,
+    /// The minimum total staked amount for the reward to accrue, one whole staked token.
+    /// It bounds the reward index a tiny stake would otherwise inflate
+    pub min_staked_amount: u64,
+    /// The emission that accrued to nobody while less than the minimum total amount was staked, in base units.
+    /// It goes back to the admin when the pool is closed
+    pub unallocated_reward: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct TokenStakeRecord {
+    /// The bump seed for the token stake record account
+    pub bump: u8,
+    /// The token stake cfg
+    pub token_stake_cfg: Pubkey,
+    /// The staker
+    pub staker: Pubkey,
+    /// The staked token amount, in base units
+    pub amount: u64,
+    /// The latest deposit at
+    pub staked_at: i64,
+    /// The withdraw at
+    pub withdraw_at: i64,
+    /// The pool reward index at the latest deposit or withdraw
+    pub reward_index: u128,
+    /// The reward accrued before the latest deposit or withdraw and not paid yet, scaled by REWARD_INDEX_PRECISION
+    pub pending_reward: u128,
 }
 
 impl StakingCfg {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         bump: u8,
         admin: Pubkey,
+        pool_id: u64,
         group_mint: Pubkey,
         reward_token_mint: Pubkey,
         reward_vault_authority_bump: u8,
         nft_vault_authority_bump: u8,
         max_staked_amount: u64,
+        max_per_wallet: u64,
         staked_start_time: i64,
         staked_end_time: i64,
         latest_reward: u64,
+        reward_period: RewardPeriod,
+        reward_mode: RewardMode,
         latest_reward_time: i64,
         minimum_period: i64,
     ) -> Self {
         Self {
             is_active: true,
+            pause_flags: PauseFlags::default(),
+            paused_at: 0,
             bump,
             admin,
+            pending_admin: Pubkey::default(),
+            roles: PoolRoles::default(),
+            creator: admin,
+            pool_id,
             group_mint,
             reward_token_mint,
             reward_vault_authority_bump,
             nft_vault_authority_bump,
             max_staked_amount,
+            max_per_wallet,
             staked_start_time,
             staked_end_time,
             latest_reward,
+            reward_period,
+            reward_mode,
             latest_reward_time,
             minimum_period,
+            penalty_policy: PenaltyPolicy::Forfeit,
+            penalty_rate: 0,
+            treasury: Pubkey::default(),
+            total_owed: 0,
             accumulated_reward: 0,
             total_reward_based_on_staked_time: 0,
             staked_amount: 0,
             updated_times: 0,
+            reward_streams: Vec::new(),
+            lock_tiers: vec![LockTier::init(0, BASE_WEIGHT)],
+        }
+    }
+
+    /// Checks the staked weight stays within the max staked amount, an nft of twice the base weight uses two slots
+    pub fn check_max_staked_amount(&self) -> Result<()> {
+        let max_staked_weight = self
+            .max_staked_amount
+            .checked_mul(BASE_WEIGHT)
+            .ok_or(StakingError::ProgramMulError)?;
+        require_gte!(max_staked_weight, self.staked_amount, StakingError::MaxStakedAmountExceeded);
+        Ok(())
+    }
+
+    pub fn check_max_per_wallet(&self, staked_count: u32) -> Result<()> {
+        if self.max_per_wallet > 0 {
+            require_gte!(self.max_per_wallet, staked_count as u64, StakingError::MaxPerWalletExceeded);
+        }
+        Ok(())
+    }
+
+    pub fn set_roles(&mut self, roles: PoolRoles) -> Result<()> {
+        require_gte!(
+            roles.max_operator_reward,
+            roles.min_operator_reward,
+            StakingError::InvalidRoles
+        );
+        self.roles = roles;
+        Ok(())
+    }
+
+    pub fn is_operator(&self, key: &Pubkey) -> bool {
+        *key == self.admin || (self.roles.operator != Pubkey::default() && *key == self.roles.operator)
+    }
+
+    /// The treasurer, the admin when no treasurer is set
+    pub fn treasurer(&self) -> Pubkey {
+        if self.roles.treasurer == Pubkey::default() {
+            self.admin
+        } else {
+            self.roles.treasurer
+        }
+    }
+
+    pub fn is_treasurer(&self, key: &Pubkey) -> bool {
+        *key == self.treasurer()
+    }
+
+    pub fn is_pauser(&self, key: &Pubkey) -> bool {
+        *key == self.admin || (self.roles.pauser != Pubkey::default() && *key == self.roles.pauser)
+    }
+
+    /// Adds `pause_flags` to the paused actions, the accrual freezes from `current_time` once frozen.
+    pub fn pause(&mut self, pause_flags: PauseFlags, current_time: i64) {
+        let was_frozen = self.pause_flags.is_accrual_frozen();
+        self.pause_flags = PauseFlags {
+            stake: self.pause_flags.stake || pause_flags.stake,
+            withdraw_reward: self.pause_flags.withdraw_reward || pause_flags.withdraw_reward,
+            unstake: self.pause_flags.unstake || pause_flags.unstake,
+            freeze_accrual: self.pause_flags.freeze_accrual || pause_flags.freeze_accrual,
+        };
+        if !was_frozen {
+            self.paused_at = current_time;
+        }
+    }
+
+    /// Freezes the accrual at the last checkpoint of the pool and of every stream and pauses staking.
+    /// It is used when the pool can not be checkpointed before the staked weight changes, the emission
+    /// since the last checkpoint is then never split over the changed weight.
+    pub fn freeze_accrual_at_checkpoint(&mut self) {
+        self.pause_flags.stake = true;
+        self.pause_flags.freeze_accrual = true;
+        self.paused_at = 0;
+    }
+
+    /// Removes `pause_flags` from the paused actions, the pool must be checkpointed before.
+    pub fn resume(&mut self, pause_flags: PauseFlags, current_time: i64) {
+        self.pause_flags = PauseFlags {
+            stake: self.pause_flags.stake && !pause_flags.stake,
+            withdraw_reward: self.pause_flags.withdraw_reward && !pause_flags.withdraw_reward,
+            unstake: self.pause_flags.unstake && !pause_flags.unstake,
+            freeze_accrual: self.pause_flags.freeze_accrual && !pause_flags.freeze_accrual,
+        };
+        self.paused_at = current_time;
+    }
+
+    /// Returns the time the reward accrues up to, never past the pause time while the accrual is frozen.
+    pub fn accrual_time(&self, time: i64, latest_reward_time: i64) -> i64 {
+        if self.pause_flags.is_accrual_frozen() {
+            time.min(self.paused_at.max(latest_reward_time))
+        } else {
+            time
+        }
+    }
+
+    /// Checks an operator other than the admin only changes the reward, within the operator bounds.
+    pub fn check_operator_update(
+        &self,
+        key: &Pubkey,
+        reward: Option<u64>,
+        has_other_updates: bool,
+    ) -> Result<()> {
+        if *key == self.admin {
+            return Ok(());
+        }
+        require!(!has_other_updates, StakingError::OperatorUpdateOutOfBounds);
+        if let Some(reward) = reward {
+            require!(
+                reward >= self.roles.min_operator_reward && reward <= self.roles.max_operator_reward,
+                StakingError::OperatorUpdateOutOfBounds
+            );
         }
+        Ok(())
+    }
+
+    /// Proposes `pending_admin` as the next admin, the default key cancels the proposal.
+    pub fn propose_admin(&mut self, pending_admin: Pubkey) -> Result<()> {
+        require_keys_neq!(pending_admin, self.admin, StakingError::InvalidPendingAdmin);
+        self.pending_admin = pending_admin;
+        Ok(())
+    }
+
+    /// Hands the pool over to the pending admin once it signs.
+    pub fn accept_admin(&mut self, pending_admin: Pubkey) -> Result<()> {
+        require!(self.pending_admin != Pubkey::default(), StakingError::InvalidPendingAdmin);
+        require_keys_eq!(pending_admin, self.pending_admin, StakingError::InvalidPendingAdmin);
+        self.admin = pending_admin;
+        self.pending_admin = Pubkey::default();
+        Ok(())
+    }
+
+    pub fn set_penalty_policy(
+        &mut self,
+        penalty_policy: PenaltyPolicy,
+        penalty_rate: u16,
+        treasury: Option<Pubkey>,
+    ) -> Result<()> {
+        require_gte!(MAX_PENALTY_RATE, penalty_rate, StakingError::InvalidPenaltyPolicy);
+        if penalty_policy == PenaltyPolicy::Treasury {
+            require!(treasury.is_some(), StakingError::InvalidTreasury);
+        }
+        self.penalty_policy = penalty_policy;
+        self.penalty_rate = penalty_rate;
+        self.treasury = treasury.unwrap_or_default();
+        Ok(())
+    }
+
+    pub fn set_lock_tiers(&mut self, lock_tiers: Vec<LockTier>) -> Result<()> {
+        require!(
+            !lock_tiers.is_empty() && lock_tiers.len() <= MAX_LOCK_TIERS,
+            StakingError::InvalidLockTier
+        );
+        for lock_tier in lock_tiers.iter() {
+            require_gte!(lock_tier.lock_period, 0, StakingError::InvalidLockTier);
+            require_gt!(lock_tier.multiplier, 0, StakingError::InvalidLockTier);
+        }
+        self.lock_tiers = lock_tiers;
+        Ok(())
+    }
+
+    /// Moves the reward index to `reward_time`, the rate applies from then on
+    pub fn checkpoint_reward(&mut self, accumulated_reward: u128, latest_reward: u64, reward_time: i64) {
+        self.accumulated_reward = accumulated_reward;
+        self.latest_reward = latest_reward;
+        self.latest_reward_time = reward_time;
+    }
+
+    pub fn increase_total_owed(&mut self, amount: u64) -> Result<()> {
+        self.total_owed = self
+            .total_owed
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        Ok(())
+    }
+
+    pub fn decrease_total_owed(&mut self, amount: u64) -> Result<()> {
+        self.total_owed = self
+            .total_owed
+            .checked_sub(amount)
+            .ok_or(StakingError::ProgramSubError)?;
+        Ok(())
     }
 
     pub fn decrease_staked_amount(&mut self, amount: u64) -> Result<()> {
@@ -132,7 +671,7 @@ impl StakingCfg {
         Ok(())
     }
 
-    pub fn increase_total_reward_based_on_staked_time(&mut self, reward: u64) -> Result<()> {
+    pub fn increase_total_reward_based_on_staked_time(&mut self, reward: u128) -> Result<()> {
         self.total_reward_based_on_staked_time = self
             .total_reward_based_on_staked_time
             .checked_add(reward)
@@ -140,7 +679,7 @@ impl StakingCfg {
         Ok(())
     }
 
-    pub fn decrease_total_reward_based_on_staked_time(&mut self, reward: u64) -> Result<()> {
+    pub fn decrease_total_reward_based_on_staked_time(&mut self, reward: u128) -> Result<()> {
         self.total_reward_based_on_staked_time = self
             .total_reward_based_on_staked_time
             .checked_sub(reward)
@@ -148,10 +687,128 @@ impl StakingCfg {
         Ok(())
     }
 
+    /// Removes the weight and the checkpoints of an emergency unstaked record.
+    /// It saturates so a drifted total never keeps the nft in the pool.
+    pub fn forfeit_staked_record(&mut self, staked_record: &StakedRecord) {
+        for (index, stream) in self.reward_streams.iter_mut().enumerate() {
+            stream.total_reward_based_on_staked_time = stream
+                .total_reward_based_on_staked_time
+                .saturating_sub(staked_record.stream_reward_checkpoint(index));
+        }
+        self.staked_amount = self.staked_amount.saturating_sub(staked_record.weight);
+        self.total_reward_based_on_staked_time = self
+            .total_reward_based_on_staked_time
+            .saturating_sub(staked_record.reward_based_on_staked_time);
+    }
+
+    pub fn has_active_reward_streams(&self, current_time: i64) -> bool {
+        self.reward_streams
+            .iter()
+            .any(|stream| stream.staked_end_time > current_time)
+    }
+
+    /// Closes the pool, the pool must be checkpointed before.
+    /// The pause is lifted since a closed pool can not be resumed, so the stakers can still unstake.
     pub fn close_staking(&mut self, staked_end_time: i64) -> Result<()> {
         self.is_active = false;
         self.staked_end_time = staked_end_time;
         self.minimum_period = 0;
+        self.pause_flags = PauseFlags::default();
+        self.paused_at = 0;
+        Ok(())
+    }
+}
+
+impl RewardPeriod {
+    /// The number of pool clock ticks in one reward period
+    pub fn ticks(&self) -> u64 {
+        match self {
+            RewardPeriod::Second => 1,
+            RewardPeriod::Hour => 60 * 60,
+            RewardPeriod::Day => 60 * 60 * 24,
+            RewardPeriod::Epoch => 1,
+        }
+    }
+
+    /// The current pool time, the unix timestamp or the epoch for epoch based pools
+    pub fn current_time(&self) -> Result<i64> {
+        let clock = Clock::get()?;
+        match self {
+            RewardPeriod::Epoch => {
+                i64::try_from(clock.epoch).map_err(|_| StakingError::FailedTimeConversion.into())
+            }
+            _ => Ok(clock.unix_timestamp),
+        }
+    }
+}
+
+impl LockTier {
+    pub fn init(lock_period: i64, multiplier: u64) -> Self {
+        Self {
+            lock_period,
+            multiplier,
+        }
+    }
+
+    /// Applies the tier multiplier to the weight of the staked nft
+    pub fn apply_multiplier(&self, weight: u64) -> Result<u64> {
+        let weight = (weight as u128)
+            .checked_mul(self.multiplier as u128)
+            .ok_or(StakingError::ProgramMulError)?
+            .checked_div(BASE_WEIGHT as u128)
+            .ok_or(StakingError::ProgramDivError)?;
+        u64::try_from(weight).map_err(|_| StakingError::InvalidLockTier.into())
+    }
+}
+
+impl RewardMode {
+    /// The number of staked nft slots the reward rate has to be funded for
+    pub fn reward_slots(&self, max_staked_amount: u64) -> u64 {
+        match self {
+            RewardMode::PerNft => max_staked_amount,
+            RewardMode::Shared => 1,
+        }
+    }
+}
+
+impl RewardStream {
+    pub fn init(
+        reward_token_mint: Pubkey,
+        token_program: Pubkey,
+        staked_end_time: i64,
+        latest_reward: u64,
+        latest_reward_time: i64,
+    ) -> Self {
+        Self {
+            reward_token_mint,
+            token_program,
+            staked_end_time,
+            latest_reward,
+            latest_reward_time,
+            accumulated_reward: 0,
+            total_reward_based_on_staked_time: 0,
+        }
+    }
+
+    /// Moves the stream reward index to `reward_time`, capped at the stream end time
+    pub fn checkpoint_reward(&mut self, accumulated_reward: u128, reward_time: i64) {
+        self.accumulated_reward = accumulated_reward;
+        self.latest_reward_time = reward_time.min(self.staked_end_time).max(self.latest_reward_time);
+    }
+
+    pub fn increase_total_reward_based_on_staked_time(&mut self, reward: u128) -> Result<()> {
+        self.total_reward_based_on_staked_time = self
+            .total_reward_based_on_staked_time
+            .checked_add(reward)
+            .ok_or(StakingError::ProgramAddError)?;
+        Ok(())
+    }
+
+    pub fn decrease_total_reward_based_on_staked_time(&mut self, reward: u128) -> Result<()> {
+        self.total_reward_based_on_staked_time = self
+            .total_reward_based_on_staked_time
+            .checked_sub(reward)
+            .ok_or(StakingError::ProgramSubError)?;
         Ok(())
     }
 }
@@ -167,13 +824,192 @@ impl CfgUpdateRecord {
     }
 }
 
+impl EmissionSchedule {
+    pub fn init(bump: u8, staking_cfg: Pubkey, segments: Vec<RateSegment>) -> Self {
+        Self {
+            bump,
+            staking_cfg,
+            segments,
+        }
+    }
+}
+
+impl EmissionSchedule {
+    pub fn raise_future_rewards(&mut self, current_time: i64, reward: u64) -> Result<()> {
+        for segment in self
+            .segments
+            .iter_mut()
+            .filter(|segment| segment.start_time > current_time)
+        {
+            segment.reward = segment
+                .reward
+                .checked_add(reward)
+                .ok_or(StakingError::ProgramAddError)?;
+        }
+        Ok(())
+    }
+}
+
+impl SponsorRecord {
+    pub fn add_funding(
+        &mut self,
+        bump: u8,
+        staking_cfg: Pubkey,
+        sponsor: Pubkey,
+        amount: u64,
+        funded_at: i64,
+    ) -> Result<()> {
+        self.bump = bump;
+        self.staking_cfg = staking_cfg;
+        self.sponsor = sponsor;
+        self.total_funded = self
+            .total_funded
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        self.funded_times = self
+            .funded_times
+            .checked_add(1)
+            .ok_or(StakingError::ProgramAddError)?;
+        self.last_funded_at = funded_at;
+        Ok(())
+    }
+}
+
+impl StakerPosition {
+    pub fn add_stake(
+        &mut self,
+        bump: u8,
+        staking_cfg: Pubkey,
+        staker: Pubkey,
+        count: u32,
+        weight: u64,
+        staked_at: i64,
+    ) -> Result<()> {
+        self.bump = bump;
+        self.staking_cfg = staking_cfg;
+        self.staker = staker;
+        if self.first_staked_at == 0 {
+            self.first_staked_at = staked_at;
+        }
+        self.staked_count = self
+            .staked_count
+            .checked_add(count)
+            .ok_or(StakingError::ProgramAddError)?;
+        self.staked_weight = self
+            .staked_weight
+            .checked_add(weight)
+            .ok_or(StakingError::ProgramAddError)?;
+        Ok(())
+    }
+
+    pub fn remove_stake(&mut self, count: u32, weight: u64) -> Result<()> {
+        self.staked_count = self
+            .staked_count
+            .checked_sub(count)
+            .ok_or(StakingError::ProgramSubError)?;
+        self.staked_weight = self
+            .staked_weight
+            .checked_sub(weight)
+            .ok_or(StakingError::ProgramSubError)?;
+        Ok(())
+    }
+
+    /// Removes an emergency unstaked nft, saturating like the pool totals.
+    pub fn forfeit_stake(&mut self, count: u32, weight: u64) {
+        self.staked_count = self.staked_count.saturating_sub(count);
+        self.staked_weight = self.staked_weight.saturating_sub(weight);
+    }
+
+    pub fn add_claimed(&mut self, amount: u64) -> Result<()> {
+        self.total_claimed = self
+            .total_claimed
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        Ok(())
+    }
+}
+
+impl RewardDebt {
+    pub fn add_owed(
+        &mut self,
+        bump: u8,
+        staking_cfg: Pubkey,
+        staker: Pubkey,
+        amount: u64,
+        updated_at: i64,
+    ) -> Result<()> {
+        self.bump = bump;
+        self.staking_cfg = staking_cfg;
+        self.staker = staker;
+        self.owed = self
+            .owed
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        self.updated_at = updated_at;
+        Ok(())
+    }
+
+    pub fn pay(&mut self, amount: u64, updated_at: i64) -> Result<()> {
+        self.owed = self
+            .owed
+            .checked_sub(amount)
+            .ok_or(StakingError::ProgramSubError)?;
+        self.updated_at = updated_at;
+        Ok(())
+    }
+}
+
+impl WeightTable {
+    pub fn init(bump: u8, staking_cfg: Pubkey) -> Self {
+        Self {
+            bump,
+            staking_cfg,
+            metadata_key: String::new(),
+            default_weight: BASE_WEIGHT,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        metadata_key: String,
+        default_weight: u64,
+        entries: Vec<WeightEntry>,
+    ) -> Result<()> {
+        require_gte!(MAX_METADATA_LEN, metadata_key.len(), StakingError::InvalidWeightTable);
+        require_gte!(MAX_WEIGHT_ENTRIES, entries.len(), StakingError::InvalidWeightTable);
+        require_gt!(default_weight, 0, StakingError::InvalidWeightTable);
+        for entry in entries.iter() {
+            require_gte!(MAX_METADATA_LEN, entry.value.len(), StakingError::InvalidWeightTable);
+            require_gt!(entry.weight, 0, StakingError::InvalidWeightTable);
+        }
+
+        self.metadata_key = metadata_key;
+        self.default_weight = default_weight;
+        self.entries = entries;
+        Ok(())
+    }
+
+    pub fn weight_of(&self, value: Option<&str>) -> u64 {
+        value
+            .and_then(|value| self.entries.iter().find(|entry| entry.value == value))
+            .map_or(self.default_weight, |entry| entry.weight)
+    }
+}
+
 impl StakedRecord {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         bump: u8,
         staker: Pubkey,
         nft_mint: Pubkey,
         staked_at: i64,
-        reward_based_on_staked_time: u64,
+        weight: u64,
+        lock_tier: u8,
+        lock_end_time: i64,
+        soft_staked: bool,
+        reward_based_on_staked_time: u128,
+        stream_reward_based_on_staked_time: Vec<u128>,
     ) -> Self {
         Self {
             bump,
@@ -181,13 +1017,33 @@ impl StakedRecord {
             nft_mint,
             staked_at,
             withdraw_at: staked_at,
+            weight,
+            lock_tier,
+            lock_end_time,
+            soft_staked,
             reward_based_on_staked_time,
+            stream_reward_based_on_staked_time,
+        }
+    }
+
+    /// Streams added after the nft was staked start from a zero checkpoint
+    pub fn stream_reward_checkpoint(&self, index: usize) -> u128 {
+        self.stream_reward_based_on_staked_time
+            .get(index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_stream_reward_checkpoint(&mut self, index: usize, checkpoint: u128) {
+        if self.stream_reward_based_on_staked_time.len() <= index {
+            self.stream_reward_based_on_staked_time.resize(index + 1, 0);
         }
+        self.stream_reward_based_on_staked_time[index] = checkpoint;
     }
 
     pub fn refresh_for_withdraw(
         &mut self,
-        reward_based_on_staked_time: u64,
+        reward_based_on_staked_time: u128,
         withdraw_at: i64,
     ) -> Result<()> {
         self.reward_based_on_staked_time = reward_based_on_staked_time;
@@ -195,3 +1051,143 @@ impl StakedRecord {
         Ok(())
     }
 }
+
+impl TokenStakeCfg {
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        bump: u8,
+        admin: Pubkey,
+        token_mint: Pubkey,
+        reward_token_mint: Pubkey,
+        reward_vault_authority_bump: u8,
+        token_vault_authority_bump: u8,
+        staked_start_time: i64,
+        staked_end_time: i64,
+        latest_reward: u64,
+        reward_period: RewardPeriod,
+        latest_reward_time: i64,
+        min_staked_amount: u64,
+    ) -> Self {
+        Self {
+            bump,
+            is_active: true,
+            admin,
+            pending_admin: Pubkey::default(),
+            creator: admin,
+            token_mint,
+            reward_token_mint,
+            reward_vault_authority_bump,
+            token_vault_authority_bump,
+            staked_start_time,
+            staked_end_time,
+            latest_reward,
+            reward_period,
+            latest_reward_time,
+            accumulated_reward: 0,
+            staked_amount: 0,
+            min_staked_amount,
+            unallocated_reward: 0,
+        }
+    }
+
+    pub fn checkpoint_reward(&mut self, accumulated_reward: u128, reward_time: i64) {
+        self.accumulated_reward = accumulated_reward;
+        self.latest_reward_time = reward_time.min(self.staked_end_time).max(self.latest_reward_time);
+    }
+
+    /// Proposes `pending_admin` as the next admin, the default key cancels the proposal.
+    pub fn propose_admin(&mut self, pending_admin: Pubkey) -> Result<()> {
+        require_keys_neq!(pending_admin, self.admin, StakingError::InvalidPendingAdmin);
+        self.pending_admin = pending_admin;
+        Ok(())
+    }
+
+    /// Hands the token pool over to the pending admin once it signs.
+    pub fn accept_admin(&mut self, pending_admin: Pubkey) -> Result<()> {
+        require!(self.pending_admin != Pubkey::default(), StakingError::InvalidPendingAdmin);
+        require_keys_eq!(pending_admin, self.pending_admin, StakingError::InvalidPendingAdmin);
+        self.admin = pending_admin;
+        self.pending_admin = Pubkey::default();
+        Ok(())
+    }
+
+    pub fn increase_staked_amount(&mut self, amount: u64) -> Result<()> {
+        self.staked_amount = self
+            .staked_amount
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        Ok(())
+    }
+
+    pub fn decrease_staked_amount(&mut self, amount: u64) -> Result<()> {
+        self.staked_amount = self
+            .staked_amount
+            .checked_sub(amount)
+            .ok_or(StakingError::ProgramSubError)?;
+        Ok(())
+    }
+
+    pub fn increase_unallocated_reward(&mut self, amount: u64) -> Result<()> {
+        self.unallocated_reward = self
+            .unallocated_reward
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        Ok(())
+    }
+
+    /// Closes the token pool, the pool must be checkpointed before.
+    /// The deposits stop and the staked tokens can still be withdrawn.
+    pub fn close_staking(&mut self, staked_end_time: i64) {
+        self.is_active = false;
+        self.staked_end_time = staked_end_time;
+        self.unallocated_reward = 0;
+    }
+}
+
+impl TokenStakeRecord {
+    /// Adds `amount` and restarts from `reward_index`, the reward accrued so far is kept as pending.
+    #[allow(clippy::too_many_arguments)]
+    pub fn deposit(
+        &mut self,
+        bump: u8,
+        token_stake_cfg: Pubkey,
+        staker: Pubkey,
+        amount: u64,
+        reward_index: u128,
+        pending_reward: u128,
+        staked_at: i64,
+    ) -> Result<()> {
+        self.bump = bump;
+        self.token_stake_cfg = token_stake_cfg;
+        self.staker = staker;
+        self.amount = self
+            .amount
+            .checked_add(amount)
+            .ok_or(StakingError::ProgramAddError)?;
+        self.reward_index = reward_index;
+        self.pending_reward = pending_reward;
+        self.staked_at = staked_at;
+        if self.withdraw_at == 0 {
+            self.withdraw_at = staked_at;
+        }
+        Ok(())
+    }
+
+    /// Removes `amount` once the accrued reward is paid, restarting from `reward_index` with the unpaid dust.
+    pub fn withdraw(
+        &mut self,
+        amount: u64,
+        reward_index: u128,
+        pending_reward: u128,
+        withdraw_at: i64,
+    ) -> Result<()> {
+        self.amount = self
+            .amount
+            .checked_sub(amount)
+            .ok_or(StakingError::ProgramSubError)?;
+        self.reward_index = reward_index;
+        self.pending_reward = pending_reward;
+        self.withdraw_at = withdraw_at;
+        Ok(())
+    }
+}
@@ -1,23 +1,72 @@
+pub mod nft_helper;
 pub mod reward_helper;
+pub mod reward_stream_helper;
+pub mod token_stake_helper;
 use anchor_lang::prelude::*;
-use crate::error::*;
+use crate::{
+    error::*,
+    state::{RateSegment, RewardPeriod, StakingCfg, REWARD_INDEX_PRECISION},
+    utils::{
+        reward_helper::{
+            calculate_current_reward_from_genesis,
+            calculate_current_reward_rate,
+            calculate_pool_reward_index,
+        },
+        reward_stream_helper::calculate_stream_reward_indices,
+    },
+};
 
 
 pub fn calc_total_reward_emission(
     reward: u64,
-    max_staked_amount: u64,
+    reward_period: RewardPeriod,
+    segments: &[RateSegment],
+    reward_slots: u64,
     staked_start_time: i64,
     staked_end_time: i64,
 ) -> Result<u64> {
-    let total_staked_period = staked_end_time.checked_sub(staked_start_time).ok_or(StakingError::ProgramSubError)?;
+    require_gt!(staked_end_time, staked_start_time, StakingError::InvalidStakedEndTime);
 
-    let total_staked_period_u64: u64 = match total_staked_period.try_into() {
-        Ok(v) => v,
-        Err(_) => return Err(StakingError::FailedTimeConversion.into()),
-    };
+    let single_reward_emission = calculate_current_reward_from_genesis(
+        0,
+        staked_end_time,
+        reward,
+        staked_start_time,
+        reward_period,
+        segments,
+    )?;
 
-    let total_reward_emission = reward.checked_mul(total_staked_period_u64).ok_or(StakingError::ProgramMulError)?;
-    let total_reward_emission = total_reward_emission.checked_mul(max_staked_amount).ok_or(StakingError::ProgramMulError)?;
+    let total_reward_emission = single_reward_emission.checked_mul(reward_slots as u128).ok_or(StakingError::ProgramMulError)?;
+    let total_reward_emission = total_reward_emission.div_ceil(REWARD_INDEX_PRECISION);
 
-    Ok(total_reward_emission)
-}
\ No newline at end of file
+    u64::try_from(total_reward_emission).map_err(|_| StakingError::RewardConversionFailed.into())
+}
+
+/// Checkpoints the pool and stream reward indices at `current_time` so a change of the
+/// staked weight only applies from then on. Returns the pool and stream reward indices.
+pub fn checkpoint_rewards(
+    staking_cfg: &mut StakingCfg,
+    current_time: i64,
+    segments: &[RateSegment],
+) -> Result<(u128, Vec<u128>)> {
+    let reward_time = current_time.min(staking_cfg.staked_end_time);
+
+    let reward_index = calculate_pool_reward_index(staking_cfg, reward_time, segments)?;
+    let current_reward = calculate_current_reward_rate(
+        reward_time,
+        staking_cfg.latest_reward,
+        staking_cfg.latest_reward_time,
+        segments,
+    );
+    let stream_reward_indices = calculate_stream_reward_indices(staking_cfg, current_time)?;
+
+    staking_cfg.checkpoint_reward(reward_index, current_reward, reward_time);
+    for (stream, stream_reward_index) in staking_cfg
+        .reward_streams
+        .iter_mut()
+        .zip(&stream_reward_indices)
+    {
+        stream.checkpoint_reward(*stream_reward_index, current_time);
+    }
+    Ok((reward_index, stream_reward_indices))
+}
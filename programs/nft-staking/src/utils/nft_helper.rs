@@ -0,0 +1,129 @@
+use crate::error::StakingError;
+use crate::state::{StakedRecord, StakingCfg, WeightTable, STAKED_RECORD_SEED};
+use crate::utils::reward_helper::calculate_weighted_reward;
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{get_mint_extension_data, Mint};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, StateWithExtensions},
+    state::Mint as Mint2022,
+};
+use spl_token_group_interface::state::TokenGroupMember;
+use spl_token_metadata_interface::state::TokenMetadata;
+
+/// The accounts passed as remaining accounts for every nft of a batch:
+/// the nft mint, the staker's nft token account, the nft vault and the staked record.
+pub const NFT_ACCOUNTS_LEN: usize = 4;
+
+/// Loads a staked record of the pool from the remaining accounts and checks it belongs to `staker`.
+pub fn load_staked_record<'info>(
+    account: &'info AccountInfo<'info>,
+    staking_cfg: &Pubkey,
+    staker: &Pubkey,
+) -> Result<Account<'info, StakedRecord>> {
+    let staked_record = Account::<StakedRecord>::try_from(account)?;
+    let staked_record_key = Pubkey::create_program_address(
+        &[
+            STAKED_RECORD_SEED.as_bytes(),
+            staking_cfg.as_ref(),
+            staked_record.nft_mint.as_ref(),
+            &[staked_record.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| StakingError::InvalidStakedRecord)?;
+    require_keys_eq!(staked_record.key(), staked_record_key, StakingError::InvalidStakedRecord);
+    require_keys_eq!(staked_record.staker, *staker, StakingError::InvalidStakedRecord);
+    Ok(staked_record)
+}
+
+/// Checks the nft is a member of the group of `group_mint`.
+pub fn validate_group_mint(nft_mint: &InterfaceAccount<Mint>, group_mint: &Pubkey) -> Result<()> {
+    let mint_extension_data = get_mint_extension_data::<TokenGroupMember>(&nft_mint.to_account_info());
+
+    msg!("mint_extension_data is ok: {:?}", mint_extension_data.is_ok());
+
+    match mint_extension_data {
+        Ok(mint_extension_data) if mint_extension_data.group == *group_mint => Ok(()),
+        _ => Err(StakingError::NftMintNotMatchGroupMint.into()),
+    }
+}
+
+/// Reads the reward weight of the nft from its metadata, in basis points.
+pub fn nft_weight(weight_table: &WeightTable, nft_mint: &InterfaceAccount<Mint>) -> Result<u64> {
+    if weight_table.metadata_key.is_empty() {
+        return Ok(weight_table.default_weight);
+    }
+
+    let nft_mint_info = nft_mint.to_account_info();
+    let mint_data = nft_mint_info.data.borrow();
+    let mint_with_extension = StateWithExtensions::<Mint2022>::unpack(&mint_data)?;
+    let metadata_value = match mint_with_extension.get_variable_len_extension::<TokenMetadata>() {
+        Ok(metadata) if metadata.mint == nft_mint.key() => metadata
+            .additional_metadata
+            .into_iter()
+            .find(|(key, _)| *key == weight_table.metadata_key)
+            .map(|(_, value)| value),
+        _ => None,
+    };
+
+    Ok(weight_table.weight_of(metadata_value.as_deref()))
+}
+
+/// Checks the nft is a member of the pool group and returns its staked record, checkpointed at
+/// `reward_index` and `stream_reward_indices`. The stream checkpoints are added to the streams,
+/// the caller adds the weight and the reward checkpoint to the pool and checks the max staked amount.
+#[allow(clippy::too_many_arguments)]
+pub fn stake_nft(
+    staking_cfg: &mut StakingCfg,
+    weight_table: &WeightTable,
+    nft_mint: &InterfaceAccount<Mint>,
+    staker: Pubkey,
+    bump: u8,
+    lock_tier: u8,
+    soft_staked: bool,
+    current_time: i64,
+    reward_index: u128,
+    stream_reward_indices: &[u128],
+) -> Result<StakedRecord> {
+    //CHECK THE GROUP MINT IS THE SAME AS THE NFT'S GROUP MINT
+    validate_group_mint(nft_mint, &staking_cfg.group_mint)?;
+
+    //READ THE NFT WEIGHT FROM THE METADATA AND APPLY THE LOCK MULTIPLIER
+    let tier = *staking_cfg
+        .lock_tiers
+        .get(lock_tier as usize)
+        .ok_or(StakingError::InvalidLockTier)?;
+    let weight = tier.apply_multiplier(nft_weight(weight_table, nft_mint)?)?;
+    require_gt!(weight, 0, StakingError::InvalidLockTier);
+    //THE LOCK ENDS WITH THE STAKING AT THE LATEST
+    let lock_end_time = current_time
+        .checked_add(tier.lock_period)
+        .ok_or(StakingError::ProgramAddError)?
+        .min(staking_cfg.staked_end_time);
+
+    //INIT THE STAKED RECORD WITH THE CHECKPOINTS OF ITS WEIGHT
+    let reward_based_on_staked_time = calculate_weighted_reward(reward_index, weight)?;
+    let mut staked_record = StakedRecord::init(
+        bump,
+        staker,
+        nft_mint.key(),
+        current_time,
+        weight,
+        lock_tier,
+        lock_end_time,
+        soft_staked,
+        reward_based_on_staked_time,
+        Vec::new(),
+    );
+    for (index, (stream, stream_reward_index)) in staking_cfg
+        .reward_streams
+        .iter_mut()
+        .zip(stream_reward_indices)
+        .enumerate()
+    {
+        let stream_reward_based_on_staked_time = calculate_weighted_reward(*stream_reward_index, weight)?;
+        staked_record.set_stream_reward_checkpoint(index, stream_reward_based_on_staked_time);
+        stream.increase_total_reward_based_on_staked_time(stream_reward_based_on_staked_time)?;
+    }
+    Ok(staked_record)
+}
@@ -1,32 +1,174 @@
 use crate::error::StakingError;
-use crate::state::{StakedRecord, StakingCfg};
+use crate::state::{
+    PenaltyPolicy, RateSegment, RewardDebt, RewardMode, RewardPeriod, StakedRecord, StakingCfg,
+    BASE_WEIGHT, MAX_PENALTY_RATE, MAX_RATE_SEGMENTS, REWARD_INDEX_PRECISION,
+};
 use anchor_lang::prelude::*;
 
-pub fn calculate_current_reward_from_genesis(
-    accumulated_reward: u64,
-    staked_end_time: i64,
-    latest_reward: u64,
-    latest_reward_time: i64,
-) -> Result<u64> {
-    let time_diff = staked_end_time
-        .checked_sub(latest_reward_time)
+pub fn calculate_reward_index_increment(
+    start_time: i64,
+    end_time: i64,
+    reward: u64,
+    reward_period: RewardPeriod,
+) -> Result<u128> {
+    let time_diff = end_time
+        .checked_sub(start_time)
         .ok_or(StakingError::InvalidTimeDiff)?;
-    let time_diff_u64 = match u64::try_from(time_diff) {
+    let time_diff_u128 = match u128::try_from(time_diff) {
         Ok(v) => v,
         Err(_) => {
             return err!(StakingError::InvalidTimeDiff);
         }
     };
-    let reward_additional = time_diff_u64
-        .checked_mul(latest_reward)
+    let reward_emission = time_diff_u128
+        .checked_mul(reward as u128)
+        .ok_or(StakingError::ProgramMulError)?;
+    mul_div(reward_emission, REWARD_INDEX_PRECISION, reward_period.ticks() as u128)
+}
+
+/// Returns `value * numerator / denominator` rounded down. The value is divided before it is
+/// multiplied so only the result has to fit in u128, the remainder is scaled separately so the
+/// result is exact.
+pub fn mul_div(value: u128, numerator: u128, denominator: u128) -> Result<u128> {
+    let quotient = value
+        .checked_div(denominator)
+        .ok_or(StakingError::ProgramDivError)?
+        .checked_mul(numerator)
         .ok_or(StakingError::ProgramMulError)?;
+    let remainder = (value % denominator)
+        .checked_mul(numerator)
+        .ok_or(StakingError::ProgramMulError)?
+        / denominator;
+    quotient
+        .checked_add(remainder)
+        .ok_or(StakingError::ProgramAddError.into())
+}
+
+/// Integrates the reward index up to `staked_end_time`, switching to the rate of each
+/// schedule segment that starts after `latest_reward_time` once its start time is reached.
+pub fn calculate_current_reward_from_genesis(
+    accumulated_reward: u128,
+    staked_end_time: i64,
+    latest_reward: u64,
+    latest_reward_time: i64,
+    reward_period: RewardPeriod,
+    segments: &[RateSegment],
+) -> Result<u128> {
+    require_gte!(staked_end_time, latest_reward_time, StakingError::InvalidTimeDiff);
+
+    let mut reward = accumulated_reward;
+    let mut segment_start_time = latest_reward_time;
+    let mut segment_reward = latest_reward;
 
+    for segment in segments
+        .iter()
+        .filter(|segment| segment.start_time > latest_reward_time)
+    {
+        if segment.start_time >= staked_end_time {
+            break;
+        }
+        let reward_additional = calculate_reward_index_increment(
+            segment_start_time,
+            segment.start_time,
+            segment_reward,
+            reward_period,
+        )?;
+        reward = reward
+            .checked_add(reward_additional)
+            .ok_or(StakingError::ProgramAddError)?;
+        segment_start_time = segment.start_time;
+        segment_reward = segment.reward;
+    }
+
+    let reward_additional = calculate_reward_index_increment(
+        segment_start_time,
+        staked_end_time,
+        segment_reward,
+        reward_period,
+    )?;
+    let reward = reward
+        .checked_add(reward_additional)
+        .ok_or(StakingError::ProgramAddError)?;
+    Ok(reward)
+}
+
+/// Splits the emission accrued since the accumulated reward between the staked weight,
+/// returning the reward index per base weight. Nothing accrues while the pool is empty.
+pub fn calculate_shared_reward_index(
+    accumulated_reward: u128,
+    reward_from_genesis: u128,
+    staked_amount: u64,
+) -> Result<u128> {
+    if staked_amount == 0 {
+        return Ok(accumulated_reward);
+    }
+    let reward_emission = reward_from_genesis
+        .checked_sub(accumulated_reward)
+        .ok_or(StakingError::ProgramSubError)?;
+    let reward_additional = mul_div(reward_emission, BASE_WEIGHT as u128, staked_amount as u128)?;
     let reward = accumulated_reward
         .checked_add(reward_additional)
         .ok_or(StakingError::ProgramAddError)?;
     Ok(reward)
 }
 
+/// Returns the pool reward index per base weight at `staked_end_time` for the pool reward mode.
+/// Shared pools must be checkpointed whenever the staked weight changes. A frozen pool stops at the pause time.
+pub fn calculate_pool_reward_index(
+    staking_cfg: &StakingCfg,
+    staked_end_time: i64,
+    segments: &[RateSegment],
+) -> Result<u128> {
+    let staked_end_time = staking_cfg.accrual_time(staked_end_time, staking_cfg.latest_reward_time);
+    let reward_from_genesis = calculate_current_reward_from_genesis(
+        staking_cfg.accumulated_reward,
+        staked_end_time,
+        staking_cfg.latest_reward,
+        staking_cfg.latest_reward_time,
+        staking_cfg.reward_period,
+        segments,
+    )?;
+    match staking_cfg.reward_mode {
+        RewardMode::PerNft => Ok(reward_from_genesis),
+        RewardMode::Shared => calculate_shared_reward_index(
+            staking_cfg.accumulated_reward,
+            reward_from_genesis,
+            staking_cfg.staked_amount,
+        ),
+    }
+}
+
+/// Returns the rate in effect at `current_time` according to the schedule.
+pub fn calculate_current_reward_rate(
+    current_time: i64,
+    latest_reward: u64,
+    latest_reward_time: i64,
+    segments: &[RateSegment],
+) -> u64 {
+    segments
+        .iter()
+        .rfind(|segment| {
+            segment.start_time > latest_reward_time && segment.start_time <= current_time
+        })
+        .map_or(latest_reward, |segment| segment.reward)
+}
+
+pub fn validate_rate_segments(
+    segments: &[RateSegment],
+    current_time: i64,
+    staked_end_time: i64,
+) -> Result<()> {
+    require_gte!(MAX_RATE_SEGMENTS, segments.len(), StakingError::InvalidEmissionSchedule);
+
+    let mut previous_start_time = current_time;
+    for segment in segments {
+        require_gt!(segment.start_time, previous_start_time, StakingError::InvalidEmissionSchedule);
+        require_gt!(staked_end_time, segment.start_time, StakingError::InvalidEmissionSchedule);
+        previous_start_time = segment.start_time;
+    }
+    Ok(())
+}
+
 pub fn enable_for_reward(
     current_time: i64,
     staked_end_time: i64,
@@ -44,51 +186,152 @@ pub fn enable_for_reward(
     Ok((staked_end_time > reward_mininum_time, staked_end_time))
 }
 
+/// Splits `reward_amount` into the part the reward vault can pay and the part owed to the staker.
+/// The reward already owed to the stakers is paid first, so `owed_amount` of the vault is reserved.
+pub fn calculate_partial_payout(reward_amount: u64, vault_amount: u64, owed_amount: u64) -> (u64, u64) {
+    let reward_paid = reward_amount.min(vault_amount.saturating_sub(owed_amount));
+    (reward_paid, reward_amount.saturating_sub(reward_paid))
+}
+
+/// Records `amount` the reward vault could not pay as owed to the staker, the reward debt
+/// account is required once the reward vault is short.
+pub fn record_owed_reward(
+    staking_cfg: &mut StakingCfg,
+    staking_cfg_key: Pubkey,
+    reward_debt: Option<&mut RewardDebt>,
+    reward_debt_bump: Option<u8>,
+    staker: Pubkey,
+    amount: u64,
+    current_time: i64,
+) -> Result<()> {
+    let (Some(reward_debt), Some(reward_debt_bump)) = (reward_debt, reward_debt_bump) else {
+        return Err(StakingError::InsufficientReward.into());
+    };
+    reward_debt.add_owed(reward_debt_bump, staking_cfg_key, staker, amount, current_time)?;
+    staking_cfg.increase_total_owed(amount)
+}
+
+/// Converts a scaled reward index amount into whole base units, rounding down.
+pub fn descale_reward(scaled_reward: u128) -> Result<u64> {
+    let reward = scaled_reward
+        .checked_div(REWARD_INDEX_PRECISION)
+        .ok_or(StakingError::ProgramDivError)?;
+    u64::try_from(reward).map_err(|_| StakingError::RewardConversionFailed.into())
+}
+
+/// Scales a reward index amount by a weight in basis points, rounding down.
+/// The total staked weight of a pool fits as the index is divided first.
+pub fn calculate_weighted_reward(reward: u128, weight: u64) -> Result<u128> {
+    mul_div(reward, weight as u128, BASE_WEIGHT as u128)
+}
+
+/// Returns the claimable reward in base units and the new weighted checkpoint of the staked record.
+/// The checkpoint only advances by the amount paid out, so sub-unit dust keeps accruing.
 pub fn calculate_reward_for_withdraw(
-    accumulated_reward: u64,
+    staking_cfg: &StakingCfg,
     staked_end_time: i64,
-    reward_from_staked_start_time: u64,
-    latest_reward: u64,
-    latest_reward_time: i64,
-) -> Result<(u64, u64)> {
-    let reward_for_staked_end_time = calculate_current_reward_from_genesis(
-        accumulated_reward,
-        staked_end_time,
-        latest_reward,
-        latest_reward_time,
-    )?;
+    reward_from_staked_start_time: u128,
+    weight: u64,
+    segments: &[RateSegment],
+) -> Result<(u64, u128)> {
+    let reward_index = calculate_pool_reward_index(staking_cfg, staked_end_time, segments)?;
+    calculate_weighted_reward_for_withdraw(reward_index, reward_from_staked_start_time, weight)
+}
+
+/// Returns the reward of a staked record of `weight` between its checkpoint and `reward_index`
+/// in base units, and the checkpoint advanced by the amount paid.
+pub fn calculate_weighted_reward_for_withdraw(
+    reward_index: u128,
+    reward_from_staked_start_time: u128,
+    weight: u64,
+) -> Result<(u64, u128)> {
+    let reward_for_staked_end_time = calculate_weighted_reward(reward_index, weight)?;
 
-    let reward_for_withdraw = reward_for_staked_end_time
+    let scaled_reward_for_withdraw = reward_for_staked_end_time
         .checked_sub(reward_from_staked_start_time)
         .ok_or(StakingError::ProgramSubError)?;
-    Ok((reward_for_withdraw, reward_for_staked_end_time))
+
+    let reward_for_withdraw = descale_reward(scaled_reward_for_withdraw)?;
+
+    let reward_checkpoint = (reward_for_withdraw as u128)
+        .checked_mul(REWARD_INDEX_PRECISION)
+        .ok_or(StakingError::ProgramMulError)?
+        .checked_add(reward_from_staked_start_time)
+        .ok_or(StakingError::ProgramAddError)?;
+    Ok((reward_for_withdraw, reward_checkpoint))
+}
+
+/// Splits the reward of an unstake before the minimum period into the payout and the penalty.
+pub fn calculate_early_unstake_reward(
+    reward_amount: u64,
+    penalty_policy: PenaltyPolicy,
+    penalty_rate: u16,
+) -> Result<(u64, u64)> {
+    if penalty_policy == PenaltyPolicy::Forfeit {
+        return Ok((0, reward_amount));
+    }
+    let penalty = (reward_amount as u128)
+        .checked_mul(penalty_rate as u128)
+        .ok_or(StakingError::ProgramMulError)?
+        .checked_div(MAX_PENALTY_RATE as u128)
+        .ok_or(StakingError::ProgramDivError)?;
+    let penalty = u64::try_from(penalty).map_err(|_| StakingError::RewardConversionFailed)?;
+    let payout = reward_amount
+        .checked_sub(penalty)
+        .ok_or(StakingError::ProgramSubError)?;
+    Ok((payout, penalty))
+}
+
+/// Adds `reward` in base units to the reward index of the remaining staked weight.
+pub fn calculate_redistributed_reward_index(
+    accumulated_reward: u128,
+    reward: u64,
+    staked_amount: u64,
+) -> Result<u128> {
+    if staked_amount == 0 {
+        return Ok(accumulated_reward);
+    }
+    let scaled_reward = (reward as u128)
+        .checked_mul(REWARD_INDEX_PRECISION)
+        .ok_or(StakingError::ProgramMulError)?;
+    let reward_additional = mul_div(scaled_reward, BASE_WEIGHT as u128, staked_amount as u128)?;
+    let reward = accumulated_reward
+        .checked_add(reward_additional)
+        .ok_or(StakingError::ProgramAddError)?;
+    Ok(reward)
 }
 
 pub fn calculate_total_reward_extend(
     latest_reward: u64,
     latest_reward_time: i64,
     staked_end_time: i64,
-    current_reward_from_genesis: u64,
+    max_staked_amount: u64,
+    current_reward_from_genesis: u128,
     staking_cfg: &StakingCfg,
+    segments: &[RateSegment],
 ) -> Result<u64> {
     let total_reward_to_staked_end_time = calculate_current_reward_from_genesis(
         current_reward_from_genesis,
         staked_end_time,
         latest_reward,
         latest_reward_time,
+        staking_cfg.reward_period,
+        segments,
     )?;
 
     let single_future_reward = total_reward_to_staked_end_time
         .checked_sub(current_reward_from_genesis)
         .ok_or(StakingError::ProgramSubError)?;
 
+    let reward_slots = staking_cfg.reward_mode.reward_slots(max_staked_amount);
     let total_future_reward = single_future_reward
-        .checked_mul(staking_cfg.max_staked_amount)
+        .checked_mul(reward_slots as u128)
         .ok_or(StakingError::ProgramMulError)?;
 
-    let total_current_reward = current_reward_from_genesis
-        .checked_mul(staking_cfg.staked_amount)
-        .ok_or(StakingError::ProgramMulError)?;
+    let total_current_reward = calculate_weighted_reward(
+        current_reward_from_genesis,
+        staking_cfg.staked_amount,
+    )?;
 
     let max_total_reward = total_future_reward
         .checked_add(total_current_reward)
@@ -98,37 +341,209 @@ pub fn calculate_total_reward_extend(
         .checked_sub(staking_cfg.total_reward_based_on_staked_time)
         .ok_or(StakingError::ProgramSubError)?;
 
-    Ok(actual_total_reward)
+    descale_reward(actual_total_reward)
+}
+
+/// Returns the reward the pool still has to pay at `current_time`, the reward the staked records
+/// have accrued but not withdrawn and the scheduled reward of every slot up to the staked end time.
+pub fn calculate_scheduled_reward_liability(
+    staking_cfg: &StakingCfg,
+    current_time: i64,
+    segments: &[RateSegment],
+) -> Result<u64> {
+    let reward_time = current_time.min(staking_cfg.staked_end_time);
+    //the rate in effect now, the later schedule segments are applied on top
+    let current_reward = calculate_current_reward_rate(
+        reward_time,
+        staking_cfg.latest_reward,
+        staking_cfg.latest_reward_time,
+        segments,
+    );
+    let current_reward_from_genesis = calculate_pool_reward_index(
+        staking_cfg,
+        reward_time,
+        segments,
+    )?;
+    calculate_total_reward_extend(
+        current_reward,
+        reward_time,
+        staking_cfg.staked_end_time,
+        staking_cfg.max_staked_amount,
+        current_reward_from_genesis,
+        staking_cfg,
+        segments,
+    )
 }
 
 pub fn calculate_reward_to_close_return(
     staked_end_time: i64,
     reward_amount: u64,
-    reward_decimals: u8,
     staking_cfg: &StakingCfg,
+    segments: &[RateSegment],
 ) -> Result<u64> {
-    let single_reward_to_staked_end_time = calculate_current_reward_from_genesis(
-        staking_cfg.accumulated_reward,
+    let single_reward_to_staked_end_time = calculate_pool_reward_index(
+        staking_cfg,
         staked_end_time,
-        staking_cfg.latest_reward,
-        staking_cfg.latest_reward_time,
+        segments,
     )?;
 
-    let total_reward_to_staked_end_time = single_reward_to_staked_end_time
-        .checked_mul(staking_cfg.staked_amount)
-        .ok_or(StakingError::ProgramMulError)?;
+    let total_reward_to_staked_end_time = calculate_weighted_reward(
+        single_reward_to_staked_end_time,
+        staking_cfg.staked_amount,
+    )?;
 
     let total_reward_to_send = total_reward_to_staked_end_time
         .checked_sub(staking_cfg.total_reward_based_on_staked_time)
         .ok_or(StakingError::ProgramSubError)?;
 
-    let total_reward_to_send_scale = total_reward_to_send
-        .checked_mul(10_u64.pow(reward_decimals as u32))
+    let total_reward_to_send = descale_reward(total_reward_to_send)?;
+
+    //a short reward vault has nothing to return, the stakers are owed what it can not pay
+    let reward_to_return = reward_amount.saturating_sub(total_reward_to_send);
+
+    Ok(reward_to_return)
+}
+
+/// Returns how many pool time ticks `amount` pays for at `reward` per period for every slot.
+pub fn calculate_end_time_extension(
+    amount: u64,
+    reward: u64,
+    reward_period: RewardPeriod,
+    reward_slots: u64,
+) -> Result<i64> {
+    let reward_per_period = (reward as u128)
+        .checked_mul(reward_slots as u128)
         .ok_or(StakingError::ProgramMulError)?;
+    let extension = (amount as u128)
+        .checked_mul(reward_period.ticks() as u128)
+        .ok_or(StakingError::ProgramMulError)?
+        .checked_div(reward_per_period)
+        .ok_or(StakingError::ProgramDivError)?;
+    i64::try_from(extension).map_err(|_| StakingError::FailedTimeConversion.into())
+}
 
-    let reward_to_return_scale = reward_amount
-        .checked_sub(total_reward_to_send_scale)
-        .ok_or(StakingError::ProgramSubError)?;
+/// Returns the reward per period per slot that `amount` adds over the remaining staking period.
+pub fn calculate_reward_raise(
+    amount: u64,
+    current_time: i64,
+    staked_end_time: i64,
+    reward_period: RewardPeriod,
+    reward_slots: u64,
+) -> Result<u64> {
+    let remaining_time = staked_end_time
+        .checked_sub(current_time)
+        .ok_or(StakingError::InvalidTimeDiff)?;
+    let remaining_time = u128::try_from(remaining_time).map_err(|_| StakingError::InvalidTimeDiff)?;
+    let total_remaining_time = remaining_time
+        .checked_mul(reward_slots as u128)
+        .ok_or(StakingError::ProgramMulError)?;
+    let reward = (amount as u128)
+        .checked_mul(reward_period.ticks() as u128)
+        .ok_or(StakingError::ProgramMulError)?
+        .checked_div(total_remaining_time)
+        .ok_or(StakingError::ProgramDivError)?;
+    u64::try_from(reward).map_err(|_| StakingError::RewardConversionFailed.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 60 * 60 * 24;
+    //100 tokens of 9 decimals per day
+    const DAILY_REWARD: u64 = 100_000_000_000;
+    //1000 nfts of the base weight
+    const MAX_STAKED_AMOUNT: u64 = 1_000;
+    const STAKED_WEIGHT: u64 = MAX_STAKED_AMOUNT * BASE_WEIGHT;
+
+    fn staking_cfg(reward_mode: RewardMode) -> StakingCfg {
+        let mut staking_cfg = StakingCfg::init(
+            0,
+            Pubkey::default(),
+            0,
+            Pubkey::default(),
+            Pubkey::default(),
+            0,
+            0,
+            MAX_STAKED_AMOUNT,
+            0,
+            0,
+            365 * DAY,
+            DAILY_REWARD,
+            RewardPeriod::Day,
+            reward_mode,
+            0,
+            0,
+        );
+        staking_cfg.staked_amount = STAKED_WEIGHT;
+        staking_cfg
+    }
+
+    #[test]
+    fn weighted_reward_is_exact() {
+        assert_eq!(calculate_weighted_reward(12_345, BASE_WEIGHT).unwrap(), 12_345);
+        assert_eq!(calculate_weighted_reward(12_345, 2 * BASE_WEIGHT).unwrap(), 24_690);
+        assert_eq!(calculate_weighted_reward(12_345, BASE_WEIGHT / 2).unwrap(), 6_172);
+        assert_eq!(calculate_weighted_reward(u128::MAX, 1).unwrap(), u128::MAX / BASE_WEIGHT as u128);
+    }
+
+    #[test]
+    fn weighted_reward_of_the_total_staked_weight_fits_after_a_year() {
+        let reward_index = calculate_reward_index_increment(0, 365 * DAY, DAILY_REWARD, RewardPeriod::Day).unwrap();
+        assert_eq!(reward_index, 365 * DAILY_REWARD as u128 * REWARD_INDEX_PRECISION);
+        //the index times the staked weight alone is above u128::MAX
+        assert!(reward_index.checked_mul(STAKED_WEIGHT as u128).is_none());
+
+        let total_reward = calculate_weighted_reward(reward_index, STAKED_WEIGHT).unwrap();
+        assert_eq!(descale_reward(total_reward).unwrap(), MAX_STAKED_AMOUNT * 365 * DAILY_REWARD);
+    }
+
+    #[test]
+    fn pool_liability_fits_after_a_year() {
+        let staking_cfg = staking_cfg(RewardMode::PerNft);
+        let total_reward = MAX_STAKED_AMOUNT * 365 * DAILY_REWARD;
+
+        //nothing is left to return when the whole period is owed to the stakers
+        let reward_to_return = calculate_reward_to_close_return(365 * DAY, total_reward, &staking_cfg, &[]).unwrap();
+        assert_eq!(reward_to_return, 0);
+        //nor when the reward vault is short of it
+        let reward_to_return = calculate_reward_to_close_return(365 * DAY, total_reward / 2, &staking_cfg, &[]).unwrap();
+        assert_eq!(reward_to_return, 0);
+
+        //half way through, the other half of the emission is still funded for every slot
+        let reward_index = calculate_pool_reward_index(&staking_cfg, 365 * DAY / 2, &[]).unwrap();
+        let total_reward_extend = calculate_total_reward_extend(
+            DAILY_REWARD,
+            365 * DAY / 2,
+            365 * DAY,
+            MAX_STAKED_AMOUNT,
+            reward_index,
+            &staking_cfg,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(total_reward_extend, total_reward);
+    }
+
+    #[test]
+    fn scheduled_reward_liability_excludes_the_withdrawn_reward() {
+        let mut staking_cfg = staking_cfg(RewardMode::PerNft);
+        let daily_liability = MAX_STAKED_AMOUNT * DAILY_REWARD;
+        assert_eq!(calculate_scheduled_reward_liability(&staking_cfg, 10 * DAY, &[]).unwrap(), 365 * daily_liability);
+
+        //the stakers withdrew the first day
+        let reward_index = calculate_pool_reward_index(&staking_cfg, DAY, &[]).unwrap();
+        staking_cfg.total_reward_based_on_staked_time = calculate_weighted_reward(reward_index, STAKED_WEIGHT).unwrap();
+        assert_eq!(calculate_scheduled_reward_liability(&staking_cfg, 10 * DAY, &[]).unwrap(), 364 * daily_liability);
+
+        //nothing is scheduled after the staked end time
+        assert_eq!(calculate_scheduled_reward_liability(&staking_cfg, 400 * DAY, &[]).unwrap(), 364 * daily_liability);
+    }
 
-    Ok(reward_to_return_scale)
+    #[test]
+    fn partial_payout_reserves_the_owed_reward() {
+        assert_eq!(calculate_partial_payout(100, 150, 0), (100, 0));
+        assert_eq!(calculate_partial_payout(100, 150, 80), (70, 30));
+        assert_eq!(calculate_partial_payout(100, 50, 80), (0, 100));
+    }
 }
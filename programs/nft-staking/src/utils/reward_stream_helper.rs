@@ -0,0 +1,212 @@
+use crate::error::StakingError;
+use crate::state::{
+    RewardMode, RewardPeriod, RewardStream, StakedRecord, StakingCfg, REWARD_INDEX_PRECISION,
+};
+use crate::utils::reward_helper::{
+    calculate_reward_index_increment, calculate_shared_reward_index, calculate_weighted_reward,
+    calculate_weighted_reward_for_withdraw, descale_reward,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_2022::{transfer_checked, TransferChecked},
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// The accounts passed as remaining accounts for every reward stream, in stream order.
+pub const REWARD_STREAM_ACCOUNTS_LEN: usize = 4;
+
+pub struct RewardStreamAccounts<'info> {
+    /// The reward token mint of the stream
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// The stream reward vault owned by the reward vault authority
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    /// The token account sending or receiving the stream reward
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    /// The token program of the stream reward token mint
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Returns the stream reward index per base weight at `current_time`, stopping at the stream end time.
+pub fn calculate_stream_reward_index(
+    stream: &RewardStream,
+    current_time: i64,
+    reward_period: RewardPeriod,
+    reward_mode: RewardMode,
+    staked_amount: u64,
+) -> Result<u128> {
+    let end_time = stream.staked_end_time.min(current_time);
+    if end_time <= stream.latest_reward_time {
+        return Ok(stream.accumulated_reward);
+    }
+    let reward_additional = calculate_reward_index_increment(
+        stream.latest_reward_time,
+        end_time,
+        stream.latest_reward,
+        reward_period,
+    )?;
+    let reward_from_genesis = stream
+        .accumulated_reward
+        .checked_add(reward_additional)
+        .ok_or(StakingError::ProgramAddError)?;
+    match reward_mode {
+        RewardMode::PerNft => Ok(reward_from_genesis),
+        RewardMode::Shared => calculate_shared_reward_index(
+            stream.accumulated_reward,
+            reward_from_genesis,
+            staked_amount,
+        ),
+    }
+}
+
+/// Returns the reward index of every stream of the pool at `current_time`, in stream order.
+pub fn calculate_stream_reward_indices(
+    staking_cfg: &StakingCfg,
+    current_time: i64,
+) -> Result<Vec<u128>> {
+    staking_cfg
+        .reward_streams
+        .iter()
+        .map(|stream| {
+            calculate_stream_reward_index(
+                stream,
+                staking_cfg.accrual_time(current_time, stream.latest_reward_time),
+                staking_cfg.reward_period,
+                staking_cfg.reward_mode,
+                staking_cfg.staked_amount,
+            )
+        })
+        .collect()
+}
+
+/// Returns the stream reward owed to the stakers at `reward_index`, in base units.
+pub fn calculate_stream_reward_liability(
+    stream: &RewardStream,
+    reward_index: u128,
+    staked_amount: u64,
+) -> Result<u64> {
+    let total_reward = calculate_weighted_reward(reward_index, staked_amount)?
+        .checked_sub(stream.total_reward_based_on_staked_time)
+        .ok_or(StakingError::ProgramSubError)?;
+    descale_reward(total_reward)
+}
+
+/// Loads the mint, vault, token account and token program of every reward stream from the remaining accounts.
+/// The token accounts must belong to `token_account_owner`.
+pub fn load_reward_stream_accounts<'info>(
+    remaining_accounts: &'info [AccountInfo<'info>],
+    reward_streams: &[RewardStream],
+    reward_vault_authority: &Pubkey,
+    token_account_owner: &Pubkey,
+) -> Result<Vec<RewardStreamAccounts<'info>>> {
+    require_eq!(
+        remaining_accounts.len(),
+        reward_streams.len() * REWARD_STREAM_ACCOUNTS_LEN,
+        StakingError::InvalidRemainingAccounts
+    );
+
+    remaining_accounts
+        .chunks(REWARD_STREAM_ACCOUNTS_LEN)
+        .zip(reward_streams)
+        .map(|(accounts, stream)| {
+            //CHECK THE STREAM TOKEN PROGRAM
+            require_keys_eq!(accounts[3].key(), stream.token_program, StakingError::InvalidRewardMint);
+            let token_program = Interface::<TokenInterface>::try_from(&accounts[3])?;
+
+            //CHECK THE STREAM MINT
+            require_keys_eq!(*accounts[0].owner, stream.token_program, StakingError::InvalidRewardMint);
+            let mint = InterfaceAccount::<Mint>::try_from(&accounts[0])?;
+            require_keys_eq!(mint.key(), stream.reward_token_mint, StakingError::InvalidRewardMint);
+
+            //CHECK THE STREAM VAULT
+            let vault_key = get_associated_token_address_with_program_id(
+                reward_vault_authority,
+                &mint.key(),
+                &stream.token_program,
+            );
+            require_keys_eq!(accounts[1].key(), vault_key, StakingError::InvalidRewardVault);
+            let vault = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+
+            //CHECK THE TOKEN ACCOUNT
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[2])?;
+            require_keys_eq!(token_account.mint, mint.key(), StakingError::InvalidRewardTokenAccount);
+            require_keys_eq!(token_account.owner, *token_account_owner, StakingError::InvalidRewardTokenAccount);
+
+            Ok(RewardStreamAccounts {
+                mint,
+                vault,
+                token_account,
+                token_program,
+            })
+        })
+        .collect()
+}
+
+/// Transfers `amount` of the stream reward from the stream vault to the token account.
+pub fn transfer_stream_reward<'info>(
+    stream_accounts: &RewardStreamAccounts<'info>,
+    reward_vault_authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    let transfer_checked_accounts = TransferChecked {
+        from: stream_accounts.vault.to_account_info(),
+        mint: stream_accounts.mint.to_account_info(),
+        to: stream_accounts.token_account.to_account_info(),
+        authority: reward_vault_authority,
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        stream_accounts.token_program.to_account_info(),
+        transfer_checked_accounts,
+        signer_seeds,
+    );
+    transfer_checked(cpi_ctx, amount, stream_accounts.mint.decimals)
+}
+
+/// Pays the stream rewards accrued by the staked record up to `stream_reward_indices`
+/// and advances its stream checkpoints. A short stream vault pays what it holds and the
+/// checkpoint only advances by the amount paid, so the rest stays accrued on the record.
+pub fn withdraw_stream_rewards<'info>(
+    reward_streams: &mut [RewardStream],
+    staked_record: &mut StakedRecord,
+    stream_reward_indices: &[u128],
+    stream_accounts: &mut [RewardStreamAccounts<'info>],
+    reward_vault_authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    for (index, ((stream, accounts), reward_index)) in reward_streams
+        .iter_mut()
+        .zip(stream_accounts.iter_mut())
+        .zip(stream_reward_indices)
+        .enumerate()
+    {
+        let stream_checkpoint = staked_record.stream_reward_checkpoint(index);
+        let (reward_amount, _) = calculate_weighted_reward_for_withdraw(
+            *reward_index,
+            stream_checkpoint,
+            staked_record.weight,
+        )?;
+
+        //pay what the stream vault holds
+        let reward_paid = reward_amount.min(accounts.vault.amount);
+        if reward_paid > 0 {
+            transfer_stream_reward(
+                accounts,
+                reward_vault_authority.clone(),
+                signer_seeds,
+                reward_paid,
+            )?;
+            accounts.vault.reload()?;
+        }
+
+        let scaled_reward_paid = (reward_paid as u128)
+            .checked_mul(REWARD_INDEX_PRECISION)
+            .ok_or(StakingError::ProgramMulError)?;
+        let reward_checkpoint = stream_checkpoint
+            .checked_add(scaled_reward_paid)
+            .ok_or(StakingError::ProgramAddError)?;
+        staked_record.set_stream_reward_checkpoint(index, reward_checkpoint);
+        stream.increase_total_reward_based_on_staked_time(scaled_reward_paid)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use crate::error::StakingError;
+use crate::state::{TokenStakeCfg, TokenStakeRecord, REWARD_INDEX_PRECISION};
+use crate::utils::reward_helper::{
+    calculate_reward_index_increment, calculate_shared_reward_index, calculate_weighted_reward,
+    descale_reward,
+};
+use anchor_lang::prelude::*;
+
+/// Returns the reward index per base weight of the token pool at `current_time`,
+/// the rate is shared by the staked tokens until the pool ends.
+/// Nothing accrues while less than the minimum total amount is staked.
+pub fn calculate_token_stake_reward_index(
+    token_stake_cfg: &TokenStakeCfg,
+    current_time: i64,
+) -> Result<u128> {
+    let end_time = token_stake_cfg.staked_end_time.min(current_time);
+    if end_time <= token_stake_cfg.latest_reward_time
+        || token_stake_cfg.staked_amount < token_stake_cfg.min_staked_amount
+    {
+        return Ok(token_stake_cfg.accumulated_reward);
+    }
+    let reward_additional = calculate_reward_index_increment(
+        token_stake_cfg.latest_reward_time,
+        end_time,
+        token_stake_cfg.latest_reward,
+        token_stake_cfg.reward_period,
+    )?;
+    let reward_from_genesis = token_stake_cfg
+        .accumulated_reward
+        .checked_add(reward_additional)
+        .ok_or(StakingError::ProgramAddError)?;
+    calculate_shared_reward_index(
+        token_stake_cfg.accumulated_reward,
+        reward_from_genesis,
+        token_stake_cfg.staked_amount,
+    )
+}
+
+/// Returns the emission of the token pool since the latest checkpoint that accrued to nobody
+/// as less than the minimum total amount is staked, in base units.
+pub fn calculate_token_stake_unallocated_reward(
+    token_stake_cfg: &TokenStakeCfg,
+    current_time: i64,
+) -> Result<u64> {
+    let end_time = token_stake_cfg.staked_end_time.min(current_time);
+    if end_time <= token_stake_cfg.latest_reward_time
+        || token_stake_cfg.staked_amount >= token_stake_cfg.min_staked_amount
+    {
+        return Ok(0);
+    }
+    let unallocated_reward = calculate_reward_index_increment(
+        token_stake_cfg.latest_reward_time,
+        end_time,
+        token_stake_cfg.latest_reward,
+        token_stake_cfg.reward_period,
+    )?;
+    descale_reward(unallocated_reward)
+}
+
+/// Checkpoints the token pool at `current_time` and returns its reward index,
+/// the emission nobody accrued is kept aside for the admin to take back on close.
+pub fn checkpoint_token_stake(
+    token_stake_cfg: &mut TokenStakeCfg,
+    current_time: i64,
+) -> Result<u128> {
+    let reward_index = calculate_token_stake_reward_index(token_stake_cfg, current_time)?;
+    let unallocated_reward = calculate_token_stake_unallocated_reward(token_stake_cfg, current_time)?;
+    token_stake_cfg.increase_unallocated_reward(unallocated_reward)?;
+    token_stake_cfg.checkpoint_reward(reward_index, current_time);
+    Ok(reward_index)
+}
+
+/// Returns the reward accrued by the token stake record up to `reward_index`, scaled by REWARD_INDEX_PRECISION.
+/// Only the index growth since the record snapshot is weighted, so it stays within the pool emission.
+pub fn calculate_token_stake_accrued_reward(
+    token_stake_record: &TokenStakeRecord,
+    reward_index: u128,
+) -> Result<u128> {
+    let reward_index_diff = reward_index
+        .checked_sub(token_stake_record.reward_index)
+        .ok_or(StakingError::ProgramSubError)?;
+    let accrued_reward = calculate_weighted_reward(reward_index_diff, token_stake_record.amount)?;
+    token_stake_record
+        .pending_reward
+        .checked_add(accrued_reward)
+        .ok_or(StakingError::ProgramAddError.into())
+}
+
+/// Checkpoints the token pool and adds `amount` to the token stake record.
+pub fn deposit_token_stake(
+    token_stake_cfg: &mut TokenStakeCfg,
+    token_stake_record: &mut TokenStakeRecord,
+    bump: u8,
+    token_stake_cfg_key: Pubkey,
+    staker: Pubkey,
+    amount: u64,
+    current_time: i64,
+) -> Result<()> {
+    require_gt!(amount, 0, StakingError::InvalidTokenAmount);
+
+    //checkpoint the reward before the staked amount changes
+    let reward_index = checkpoint_token_stake(token_stake_cfg, current_time)?;
+
+    //the record keeps the reward accrued so far and restarts from the current reward index
+    let pending_reward = calculate_token_stake_accrued_reward(token_stake_record, reward_index)?;
+    token_stake_record.deposit(
+        bump,
+        token_stake_cfg_key,
+        staker,
+        amount,
+        reward_index,
+        pending_reward,
+        current_time,
+    )?;
+    token_stake_cfg.increase_staked_amount(amount)
+}
+
+/// Checkpoints the token pool, removes `amount` from the token stake record
+/// and returns the reward to pay, in base units.
+/// The reward vault pays what it holds, the rest stays pending on the record.
+pub fn withdraw_token_stake(
+    token_stake_cfg: &mut TokenStakeCfg,
+    token_stake_record: &mut TokenStakeRecord,
+    amount: u64,
+    reward_vault_amount: u64,
+    current_time: i64,
+) -> Result<u64> {
+    require_gte!(token_stake_record.amount, amount, StakingError::InvalidTokenAmount);
+
+    //checkpoint the reward before the staked amount changes
+    let reward_index = checkpoint_token_stake(token_stake_cfg, current_time)?;
+
+    //calculate the reward, the sub-unit dust and what the reward vault can not pay stay pending
+    let accrued_reward = calculate_token_stake_accrued_reward(token_stake_record, reward_index)?;
+    let reward_amount = descale_reward(accrued_reward)?.min(reward_vault_amount);
+    let pending_reward = accrued_reward
+        .checked_sub((reward_amount as u128) * REWARD_INDEX_PRECISION)
+        .ok_or(StakingError::ProgramSubError)?;
+
+    //the remaining amount restarts from the current reward index
+    token_stake_cfg.decrease_staked_amount(amount)?;
+    token_stake_record.withdraw(amount, reward_index, pending_reward, current_time)?;
+    Ok(reward_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::RewardPeriod;
+
+    const DAY: i64 = 60 * 60 * 24;
+    const YEAR: i64 = 365 * DAY;
+    //one whole token of 9 decimals
+    const MIN_STAKED_AMOUNT: u64 = 1_000_000_000;
+    //a million tokens of 9 decimals per day
+    const DAILY_REWARD: u64 = 1_000_000_000_000_000;
+
+    fn token_stake_cfg() -> TokenStakeCfg {
+        TokenStakeCfg::init(
+            0,
+            Pubkey::default(),
+            Pubkey::default(),
+            Pubkey::default(),
+            0,
+            0,
+            0,
+            2 * YEAR,
+            DAILY_REWARD,
+            RewardPeriod::Day,
+            0,
+            MIN_STAKED_AMOUNT,
+        )
+    }
+
+    fn token_stake_record() -> TokenStakeRecord {
+        TokenStakeRecord {
+            bump: 0,
+            token_stake_cfg: Pubkey::default(),
+            staker: Pubkey::default(),
+            amount: 0,
+            staked_at: 0,
+            withdraw_at: 0,
+            reward_index: 0,
+            pending_reward: 0,
+        }
+    }
+
+    fn deposit(cfg: &mut TokenStakeCfg, record: &mut TokenStakeRecord, amount: u64, time: i64) {
+        deposit_token_stake(cfg, record, 0, Pubkey::default(), Pubkey::default(), amount, time).unwrap();
+    }
+
+    #[test]
+    fn nothing_accrues_below_the_minimum_total_stake() {
+        let mut cfg = token_stake_cfg();
+        let mut dust = token_stake_record();
+        deposit(&mut cfg, &mut dust, 1, 0);
+
+        //a single base unit can not inflate the reward index
+        assert_eq!(calculate_token_stake_reward_index(&cfg, YEAR).unwrap(), 0);
+        assert_eq!(withdraw_token_stake(&mut cfg, &mut dust, 1, u64::MAX, YEAR).unwrap(), 0);
+        assert_eq!(cfg.accumulated_reward, 0);
+        //the emission of the year is kept aside for the admin
+        assert_eq!(cfg.unallocated_reward, 365 * DAILY_REWARD);
+    }
+
+    #[test]
+    fn large_deposit_after_the_minimum_total_stake_does_not_overflow() {
+        let mut cfg = token_stake_cfg();
+        let mut minimum = token_stake_record();
+        let mut whale = token_stake_record();
+
+        //the minimum stake alone earns the whole emission for a year
+        deposit(&mut cfg, &mut minimum, MIN_STAKED_AMOUNT, 0);
+        //a billion tokens join once the index is at its largest
+        let whale_amount = 1_000_000_000 * MIN_STAKED_AMOUNT;
+        deposit(&mut cfg, &mut whale, whale_amount, YEAR);
+        //the index times the whale amount is above u128::MAX
+        assert!(cfg.accumulated_reward.checked_mul(whale_amount as u128).is_none());
+
+        let whale_reward = withdraw_token_stake(&mut cfg, &mut whale, whale_amount, u64::MAX, 2 * YEAR).unwrap();
+        let minimum_reward = withdraw_token_stake(&mut cfg, &mut minimum, MIN_STAKED_AMOUNT, u64::MAX, 2 * YEAR).unwrap();
+
+        //the rewards add up to the emission of both years, up to the rounding dust
+        let total_emission = 2 * 365 * DAILY_REWARD;
+        let total_reward = whale_reward + minimum_reward;
+        assert!(total_reward <= total_emission);
+        assert!(total_emission - total_reward <= 2);
+        assert!(minimum_reward > 365 * DAILY_REWARD);
+        assert_eq!(cfg.staked_amount, 0);
+    }
+
+    #[test]
+    fn accrued_reward_is_kept_across_deposits() {
+        let mut cfg = token_stake_cfg();
+        let mut record = token_stake_record();
+        deposit(&mut cfg, &mut record, MIN_STAKED_AMOUNT, 0);
+        deposit(&mut cfg, &mut record, MIN_STAKED_AMOUNT, DAY);
+
+        let reward = withdraw_token_stake(&mut cfg, &mut record, 2 * MIN_STAKED_AMOUNT, u64::MAX, 2 * DAY).unwrap();
+        assert_eq!(reward, 2 * DAILY_REWARD);
+        assert_eq!(record.amount, 0);
+    }
+
+    #[test]
+    fn short_reward_vault_keeps_the_rest_pending() {
+        let mut cfg = token_stake_cfg();
+        let mut record = token_stake_record();
+        deposit(&mut cfg, &mut record, MIN_STAKED_AMOUNT, 0);
+
+        //the principal leaves with what the reward vault holds
+        let reward = withdraw_token_stake(&mut cfg, &mut record, MIN_STAKED_AMOUNT, DAILY_REWARD / 4, DAY).unwrap();
+        assert_eq!(reward, DAILY_REWARD / 4);
+        assert_eq!(record.amount, 0);
+        assert_eq!(record.pending_reward, (DAILY_REWARD - DAILY_REWARD / 4) as u128 * REWARD_INDEX_PRECISION);
+
+        //the rest is paid once the reward vault holds it
+        let reward = withdraw_token_stake(&mut cfg, &mut record, 0, u64::MAX, 2 * DAY).unwrap();
+        assert_eq!(reward, DAILY_REWARD - DAILY_REWARD / 4);
+        assert_eq!(record.pending_reward, 0);
+    }
+}